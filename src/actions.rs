@@ -0,0 +1,39 @@
+//! Human-readable descriptions of actions assigned to buttons.
+
+use serde_json::Value;
+
+use crate::models::{Assignment, AssignmentAction, Settings};
+
+pub fn describe_assignment(assignment: &Assignment, settings: &Settings) -> String {
+    match assignment.action() {
+        // Card may still refer built-in action, show it as is.
+        AssignmentAction::Default => Value::Object(assignment.rest.clone()).to_string(),
+        action => describe(&action, settings),
+    }
+}
+
+pub fn describe(action: &AssignmentAction, settings: &Settings) -> String {
+    match action {
+        AssignmentAction::Default => "default".to_owned(),
+        AssignmentAction::OsShortcut { shortcut } => shortcut.to_lowercase().replace('_', "-"),
+        AssignmentAction::Keystroke { code, modifiers } => {
+            if modifiers.is_empty() {
+                format!("keystroke {code}")
+            } else {
+                let modifiers: Vec<String> = modifiers.iter().map(ToString::to_string).collect();
+                format!("keystroke {code} with modifiers {}", modifiers.join("+"))
+            }
+        }
+        AssignmentAction::Gesture { gestures } => {
+            let directions: Vec<String> = gestures.iter()
+                .map(|(direction, action)| format!("{direction}={}", describe(action, settings)))
+                .collect();
+            format!("gestures ({})", directions.join(", "))
+        }
+        AssignmentAction::SmartAction { id } => {
+            let name = settings.smart_actions.iter().flatten().find(|a| a.id == *id).map_or(id.as_str(), |a| a.name.as_str());
+            format!("Smart Action '{name}'")
+        }
+        AssignmentAction::Unknown(action) => action.to_string(),
+    }
+}
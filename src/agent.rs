@@ -0,0 +1,213 @@
+//! Control of Logi Options+ agent, the process which applies settings to devices.
+//! It reads settings on start only, so it must be restarted to pick up changes.
+
+use eyre::Result;
+
+use crate::options::{Agent, AgentCommand};
+
+pub fn agent(opts: &Agent) -> Result<()> {
+    match opts.command {
+        AgentCommand::Status => {
+            println!("{}", if is_running()? { "running" } else { "not running" });
+            Ok(())
+        }
+        AgentCommand::Restart => restart(),
+    }
+}
+
+pub fn restart() -> Result<()> {
+    platform::restart()
+}
+
+pub fn is_running() -> Result<bool> {
+    platform::is_running()
+}
+
+/// How long agent is given to exit gracefully before it is killed.
+#[cfg(any(target_os = "macos", windows))]
+const TERMINATE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Runs command, fails if it exits unsuccessfully.
+#[cfg(any(target_os = "macos", windows))]
+fn run(command: &mut std::process::Command) -> Result<()> {
+    let status = command.status()?;
+    eyre::ensure!(status.success(), "{} failed: {status}", command.get_program().to_string_lossy());
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::{process::Command, time::{Duration, Instant}};
+
+    use eyre::Result;
+
+    fn service() -> String {
+        let uid = unsafe { libc::getuid() };
+        format!("gui/{uid}/com.logi.cp-dev-mgr")
+    }
+
+    pub fn is_running() -> Result<bool> {
+        Ok(agent_pid()?.is_some())
+    }
+
+    /// Agent is asked to terminate first, so it may finish writing settings,
+    /// and is killed only if it doesn't exit in time. launchd starts it again
+    /// unless agent exited cleanly and its job isn't kept alive, it is
+    /// started explicitly then.
+    pub fn restart() -> Result<()> {
+        let Some(pid) = agent_pid()? else {
+            kickstart()?;
+            tracing::info!(service = service(), "agent wasn't running, started it");
+            return Ok(());
+        };
+
+        kill("SIGTERM")?;
+        let deadline = Instant::now() + super::TERMINATE_TIMEOUT;
+        while agent_pid()? == Some(pid) {
+            if Instant::now() > deadline {
+                tracing::info!("agent didn't exit in time, killing it");
+                kill("SIGKILL")?;
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(200));
+        }
+
+        let deadline = Instant::now() + super::TERMINATE_TIMEOUT;
+        while Instant::now() < deadline {
+            if agent_pid()?.is_some_and(|new_pid| new_pid != pid) {
+                tracing::info!(service = service(), "agent restarted by launchd");
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(200));
+        }
+        kickstart()?;
+        tracing::info!(service = service(), "launchd didn't start agent again, started it");
+        Ok(())
+    }
+
+    /// Returns process id of running agent.
+    fn agent_pid() -> Result<Option<u32>> {
+        let output = Command::new("/bin/launchctl").arg("print").arg(service()).output()?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout.lines().find_map(|line| line.trim().strip_prefix("pid = ")?.parse().ok()))
+    }
+
+    /// Starts agent unless it is running.
+    fn kickstart() -> Result<()> {
+        super::run(Command::new("/bin/launchctl").arg("kickstart").arg(service()))
+    }
+
+    fn kill(signal: &str) -> Result<()> {
+        super::run(Command::new("/bin/launchctl").args(["kill", signal]).arg(service()))
+    }
+}
+
+/// Depending on installation, agent is either registered as a service or
+/// started as plain process in user session. Service is controlled via
+/// Service Control Manager, process is killed and started again.
+#[cfg(windows)]
+mod platform {
+    use std::{path::PathBuf, process::Command, time::{Duration, Instant}};
+
+    use eyre::{bail, eyre, Result};
+
+    const SERVICE: &str = "LogiOptionsPlusAgent";
+    const PROCESS: &str = "logioptionsplus_agent.exe";
+
+    pub fn is_running() -> Result<bool> {
+        let output = Command::new("tasklist")
+            .args(["/FI", &format!("IMAGENAME eq {PROCESS}"), "/NH"])
+            .output()?;
+        Ok(String::from_utf8_lossy(&output.stdout).to_lowercase().contains(PROCESS))
+    }
+
+    pub fn restart() -> Result<()> {
+        if service_state()?.is_some() {
+            require_elevation("restart Logi Options+ agent service")?;
+            if service_state()?.as_deref() != Some("STOPPED") {
+                Command::new("sc").args(["stop", SERVICE]).output()?;
+                wait_for_state("STOPPED")?;
+            }
+            super::run(Command::new("sc").args(["start", SERVICE]))?;
+            tracing::info!(service = SERVICE, "agent service restarted");
+            return Ok(());
+        }
+
+        if is_running()? {
+            // Ask agent to exit first, so it may finish writing settings.
+            Command::new("taskkill").args(["/IM", PROCESS]).output()?;
+            let deadline = Instant::now() + super::TERMINATE_TIMEOUT;
+            while is_running()? && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(200));
+            }
+        }
+        if is_running()? {
+            tracing::info!("agent didn't exit in time, killing it");
+            let output = Command::new("taskkill").args(["/F", "/IM", PROCESS]).output()?;
+            if !output.status.success() {
+                // Agent may run under another account, killing it needs elevation then.
+                require_elevation("stop Logi Options+ agent")?;
+                bail!("can't stop agent: {}", String::from_utf8_lossy(&output.stderr).trim());
+            }
+        }
+        let program_files = std::env::var_os("ProgramFiles").ok_or_else(|| eyre!("ProgramFiles isn't set"))?;
+        let agent = PathBuf::from(program_files).join("LogiOptionsPlus").join(PROCESS);
+        Command::new(&agent).spawn()
+            .map_err(|err| eyre!("can't start agent {}: {err}", agent.display()))?;
+        tracing::info!(agent = %agent.display(), "agent process restarted");
+        Ok(())
+    }
+
+    /// Returns service state like "RUNNING" or "STOPPED", or `None` if
+    /// there is no such service.
+    fn service_state() -> Result<Option<String>> {
+        let output = Command::new("sc").args(["query", SERVICE]).output()?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let state = stdout.lines()
+            .find_map(|line| line.trim().strip_prefix("STATE"))
+            .and_then(|state| state.split_whitespace().nth(2))
+            .map(str::to_owned);
+        Ok(state)
+    }
+
+    fn wait_for_state(expected: &str) -> Result<()> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while service_state()?.as_deref() != Some(expected) {
+            if Instant::now() > deadline {
+                bail!("timed out waiting for agent service to become {expected}");
+            }
+            std::thread::sleep(Duration::from_millis(200));
+        }
+        Ok(())
+    }
+
+    fn require_elevation(action: &str) -> Result<()> {
+        // `net session` succeeds only for administrators, it's the simplest
+        // check not requiring Windows API bindings.
+        let elevated = Command::new("net").arg("session").output()?.status.success();
+        if !elevated {
+            bail!("administrator rights are needed to {action}; run logi-man from terminal started with 'Run as administrator'");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+mod platform {
+    use eyre::{bail, Result};
+
+    pub fn is_running() -> Result<bool> {
+        bail!("agent control isn't supported on this platform");
+    }
+
+    pub fn restart() -> Result<()> {
+        println!("Now you should kill Logi agent");
+        Ok(())
+    }
+}
@@ -0,0 +1,440 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicUsize, Ordering}, Mutex, OnceLock},
+    time::{Duration, SystemTime},
+};
+
+use eyre::{bail, OptionExt, Result};
+use tracing::{info, warn};
+
+use crate::{config, models::Settings, options::{self, BackupCommand}};
+
+pub fn backup(db_path: &Path, opts: &options::Backup) -> Result<()> {
+    match &opts.command {
+        BackupCommand::List => {
+            for backup in list_backups(db_path)? {
+                println!("{}", backup.display());
+            }
+            Ok(())
+        }
+        BackupCommand::Verify { files, all, jobs } => {
+            let files = if *all { list_backups(db_path)? } else { files.clone() };
+            let jobs = jobs.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, usize::from));
+            verify_all(&files, jobs)
+        }
+    }
+}
+
+/// Retention and compression of backups.
+#[derive(Clone, Copy, Default)]
+pub struct Policy {
+    /// Keep only this many latest timestamped backups.
+    pub keep: Option<u64>,
+    /// Remove backups, daily snapshots included, older than this number of days.
+    pub max_age_days: Option<u64>,
+    /// Compress database backups with gzip.
+    pub compress: bool,
+}
+
+static POLICY: OnceLock<Policy> = OnceLock::new();
+
+/// Sets backup policy, the one from configuration is used otherwise.
+pub fn set_policy(policy: Policy) {
+    let _ = POLICY.set(policy);
+}
+
+fn policy() -> Policy {
+    *POLICY.get_or_init(|| {
+        let backups = &config::get().backups;
+        Policy { keep: backups.keep, max_age_days: backups.max_age_days, compress: backups.compress }
+    })
+}
+
+/// Makes timestamped copy of database next to it.
+///
+/// When daily snapshots are enabled, the first backup of the day also
+/// makes snapshot labeled with date only, which isn't overwritten until
+/// next day, so there is coarse-grained history even when many changes are
+/// made in one session.
+///
+/// Backups exceeding retention policy are removed afterwards.
+pub fn backup_database(db_path: &Path, db: &rusqlite::Connection) -> Result<()> {
+    crate::ensure_unprotected(db_path)?;
+    let policy = policy();
+    let db_path_str = db_path.to_str().ok_or_eyre("database path isn't valid UTF-8")?;
+    checkpoint(db)?;
+
+    if config::get().daily_snapshot {
+        let today: String = db.query_row("SELECT strftime('%Y-%m-%d', 'now', 'localtime')", [], |row| row.get(0))?;
+        let snapshot = format!("{db_path_str}.daily.{today}");
+        if !Path::new(&snapshot).exists() && !Path::new(&format!("{snapshot}.gz")).exists() {
+            let file = vacuum_into(db, &snapshot, policy.compress)?;
+            info!(file, "daily snapshot created");
+        }
+    }
+
+    let timestamp: String = db.query_row("SELECT strftime('%Y-%m-%d_%H-%M-%S', 'now', 'localtime')", [], |row| row.get(0))?;
+    // Several changes may be made within a second, later backups get counter.
+    let backup = (0..)
+        .map(|n| if n == 0 { format!("{db_path_str}.{timestamp}") } else { format!("{db_path_str}.{timestamp}-{n}") })
+        .find(|backup| !Path::new(backup).exists() && !Path::new(&format!("{backup}.gz")).exists())
+        .unwrap();
+    let file = vacuum_into(db, &backup, policy.compress)?;
+    info!(file, "backup created");
+
+    if config::get().json_backup.enabled {
+        backup_json(&backup, db)?;
+    }
+    prune(db_path, &policy)
+}
+
+/// Copies database into file, compressed copy gets `.gz` suffix.
+/// Returns name of created file.
+fn vacuum_into(db: &rusqlite::Connection, file: &str, compress: bool) -> Result<String> {
+    db.execute("VACUUM INTO ?1", [file])?;
+    if !compress {
+        return Ok(file.to_owned());
+    }
+    let compressed = format!("{file}.gz");
+    let mut encoder = flate2::write::GzEncoder::new(File::create(&compressed)?, flate2::Compression::best());
+    std::io::copy(&mut File::open(file)?, &mut encoder)?;
+    encoder.finish()?;
+    std::fs::remove_file(file)?;
+    Ok(compressed)
+}
+
+/// Removes backups exceeding retention policy. Database copy and settings
+/// JSON saved with it count as one backup. Count limit applies to
+/// timestamped backups only, daily snapshots are removed by age.
+fn prune(db_path: &Path, policy: &Policy) -> Result<()> {
+    if policy.keep.is_none() && policy.max_age_days.is_none() {
+        return Ok(());
+    }
+    let name = db_path.file_name().ok_or_eyre("database path has no file name")?.to_string_lossy();
+
+    // Group files by backup they belong to.
+    let mut groups: Vec<Group> = Vec::new();
+    for path in list_backups(db_path)? {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let stem = [".json.gz", ".json", ".gz"].iter()
+            .find_map(|suffix| file_name.strip_suffix(suffix))
+            .unwrap_or(&file_name)
+            .to_owned();
+        match groups.iter_mut().find(|group| group.stem == stem) {
+            Some(group) => group.files.push(path),
+            None => {
+                let modified = path.metadata()?.modified()?;
+                groups.push(Group { time: backup_time(&stem, &name).map(str::to_owned), stem, modified, files: vec![path] });
+            }
+        }
+    }
+    // Newest backup first. Modification time changes when backups are copied,
+    // so time in name is used, modification time only breaks ties.
+    groups.sort_by(|a, b| (&b.time, b.modified).cmp(&(&a.time, a.modified)));
+
+    for group in select_pruned(&groups, &format!("{name}.daily."), policy, SystemTime::now()) {
+        for file in &group.files {
+            std::fs::remove_file(file)?;
+            info!(file = %file.display(), "old backup removed");
+        }
+    }
+    Ok(())
+}
+
+/// Files of one backup.
+struct Group {
+    /// File name without suffixes of compressed and JSON files.
+    stem: String,
+    /// Time from file name, see [`backup_time`].
+    time: Option<String>,
+    modified: SystemTime,
+    files: Vec<PathBuf>,
+}
+
+/// Returns time backup is made at as it is written in its name, like
+/// "2024-05-01_12-30-00", or date for daily snapshots. Such strings
+/// are ordered the same way as times.
+fn backup_time<'a>(stem: &'a str, db_name: &str) -> Option<&'a str> {
+    let suffix = stem.strip_prefix(db_name)?.strip_prefix('.')?;
+    let time = suffix.strip_prefix("daily.").unwrap_or(suffix);
+    let valid = time.len() >= "2024-05-01".len() && time.bytes().all(|b| b.is_ascii_digit() || b == b'-' || b == b'_');
+    valid.then_some(time)
+}
+
+/// Selects backups exceeding retention policy, backups are ordered newest first.
+fn select_pruned<'a>(groups: &'a [Group], daily_prefix: &str, policy: &Policy, now: SystemTime) -> Vec<&'a Group> {
+    let mut pruned = Vec::new();
+    let mut timestamped = 0;
+    for (index, group) in groups.iter().enumerate() {
+        let is_daily = group.stem.starts_with(daily_prefix);
+        if !is_daily {
+            timestamped += 1;
+        }
+        // The newest backup is the one just made, it is kept whatever policy says.
+        if index == 0 {
+            continue;
+        }
+        let too_many = !is_daily && policy.keep.is_some_and(|keep| timestamped > keep);
+        let too_old = policy.max_age_days.is_some_and(|days| {
+            now.duration_since(group.modified).is_ok_and(|age| age > Duration::from_secs(days.saturating_mul(24 * 60 * 60)))
+        });
+        if too_many || too_old {
+            pruned.push(group);
+        }
+    }
+    pruned
+}
+
+/// Saves settings JSON alone, it is enough to restore settings in most cases.
+fn backup_json(backup: &str, db: &rusqlite::Connection) -> Result<()> {
+    let options = &config::get().json_backup;
+    let mut json = crate::load_settings(db)?;
+    if options.canonical {
+        let mut value: serde_json::Value = serde_json::from_slice(&json)?;
+        value.sort_all_objects();
+        json = serde_json::to_vec(&value)?;
+    }
+
+    let file = if options.compress {
+        let file = format!("{backup}.json.gz");
+        let mut encoder = flate2::write::GzEncoder::new(File::create(&file)?, flate2::Compression::best());
+        encoder.write_all(&json)?;
+        encoder.finish()?;
+        file
+    } else {
+        let file = format!("{backup}.json");
+        std::fs::write(&file, &json)?;
+        file
+    };
+    info!(file, "settings backup created");
+    Ok(())
+}
+
+/// In WAL mode latest agent writes may still be in WAL file only,
+/// move them into main database file.
+fn checkpoint(db: &rusqlite::Connection) -> Result<()> {
+    if crate::journal_mode(db)?.eq_ignore_ascii_case("wal") {
+        let busy: bool = db.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))?;
+        if busy {
+            warn!("can't checkpoint WAL, database is busy");
+        } else {
+            info!("WAL checkpointed");
+        }
+    }
+    Ok(())
+}
+
+/// Returns backups of database, both database copies and settings JSON
+/// ones, oldest first.
+pub fn list_backups(db_path: &Path) -> Result<Vec<PathBuf>> {
+    let dir = db_path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = db_path.file_name().ok_or_eyre("database path has no file name")?.to_string_lossy();
+    let prefix = format!("{name}.");
+    let mut backups: Vec<(std::time::SystemTime, PathBuf)> = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let is_backup = entry.file_name().to_str()
+            .and_then(|name| name.strip_prefix(&prefix))
+            // Timestamped backups and daily snapshots, WAL files have no dot after name.
+            .is_some_and(|suffix| suffix.starts_with(|c: char| c.is_ascii_digit()) || suffix.starts_with("daily."));
+        if is_backup {
+            backups.push((entry.metadata()?.modified()?, entry.path()));
+        }
+    }
+    backups.sort();
+    Ok(backups.into_iter().map(|(_, path)| path).collect())
+}
+
+enum Status {
+    Ok,
+    /// File can't be read as database or archive.
+    Corrupt(String),
+    /// File is read, but settings in it are invalid.
+    Unparseable(String),
+}
+
+/// Verifies backups in parallel and prints table of results,
+/// fails if any backup is bad.
+fn verify_all(files: &[PathBuf], jobs: usize) -> Result<()> {
+    let progress = indicatif::ProgressBar::new(files.len() as u64);
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Status>>> = Mutex::new(files.iter().map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, files.len().max(1)) {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(file) = files.get(index) else { break };
+                    let status = verify(file);
+                    results.lock().unwrap()[index] = Some(status);
+                    progress.inc(1);
+                }
+            });
+        }
+    });
+    progress.finish_and_clear();
+
+    let (mut corrupt, mut unparseable) = (0, 0);
+    for (file, status) in files.iter().zip(results.into_inner().unwrap()) {
+        match status.unwrap() {
+            Status::Ok => println!("ok           {}", file.display()),
+            Status::Corrupt(reason) => {
+                corrupt += 1;
+                println!("corrupt      {}: {reason}", file.display());
+            }
+            Status::Unparseable(reason) => {
+                unparseable += 1;
+                println!("unparseable  {}: {reason}", file.display());
+            }
+        }
+    }
+    println!("{} ok, {corrupt} corrupt, {unparseable} unparseable", files.len() - corrupt - unparseable);
+    if corrupt + unparseable > 0 {
+        bail!("found {} bad backup(s)", corrupt + unparseable);
+    }
+    Ok(())
+}
+
+fn verify(file: &Path) -> Status {
+    let json = match load(file) {
+        Ok(Ok(json)) => json,
+        Ok(Err(err)) => return Status::Unparseable(format!("{err:#}")),
+        Err(err) => return Status::Corrupt(format!("{err:#}")),
+    };
+    match serde_json::from_slice::<Settings>(&json) {
+        Ok(_) => Status::Ok,
+        Err(err) => Status::Unparseable(err.to_string()),
+    }
+}
+
+/// Loads settings JSON from backup of any kind.
+pub fn read_settings(file: &Path) -> Result<Vec<u8>> {
+    load(file)?
+}
+
+/// Loads settings JSON from database copy or settings backup, compressed
+/// or not. Outer error means backup is corrupt, inner one that settings
+/// can't be loaded from it.
+fn load(file: &Path) -> Result<Result<Vec<u8>>> {
+    let name = file.to_string_lossy();
+    if name.ends_with(".json.gz") {
+        let mut json = Vec::new();
+        flate2::read::GzDecoder::new(File::open(file)?).read_to_end(&mut json)?;
+        Ok(Ok(json))
+    } else if name.ends_with(".json") {
+        Ok(Ok(std::fs::read(file)?))
+    } else if name.ends_with(".gz") {
+        let temp = decompress(file)?;
+        let database = read_database(&temp);
+        let _ = std::fs::remove_file(&temp);
+        database
+    } else {
+        read_database(file)
+    }
+}
+
+/// Returns timestamped backups with time they are made, oldest first.
+/// Only one file is returned for every backup, settings JSON is preferred
+/// to database copy as it is faster to read.
+pub fn snapshots(db_path: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let name = db_path.file_name().ok_or_eyre("database path has no file name")?.to_string_lossy();
+    let prefix = format!("{name}.");
+    let mut snapshots: Vec<(String, PathBuf)> = Vec::new();
+    for path in list_backups(db_path)? {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        // Backup suffix is "YYYY-MM-DD_HH-MM-SS", followed by counter when
+        // there are several backups made within a second. Daily snapshots are
+        // skipped, they are copies of the first backup of the day.
+        let Some(suffix) = file_name.strip_prefix(&prefix)
+            .filter(|suffix| !suffix.starts_with("daily.")) else { continue };
+        let suffix = suffix.split('.').next().unwrap_or_default();
+        let Some((date, time)) = suffix.get(..19).and_then(|time| time.split_once('_')) else { continue };
+        let time = format!("{date} {}{}", time.replace('-', ":"), &suffix[19..]);
+        match snapshots.iter_mut().find(|(t, _)| *t == time) {
+            Some((_, existing)) if file_name.contains(".json") => *existing = path,
+            Some(_) => {}
+            None => snapshots.push((time, path)),
+        }
+    }
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+/// Decompresses database backup into temporary file, SQLite can't open
+/// compressed one.
+fn decompress(file: &Path) -> std::io::Result<PathBuf> {
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    let temp = std::env::temp_dir().join(format!("logi-man-{}-{}", std::process::id(), name.trim_end_matches(".gz")));
+    let mut decoder = flate2::read::GzDecoder::new(File::open(file)?);
+    std::io::copy(&mut decoder, &mut File::create(&temp)?)?;
+    Ok(temp)
+}
+
+/// Checks database integrity and loads settings from it. Outer error means
+/// database is corrupt, inner one that settings can't be loaded.
+fn read_database(file: &Path) -> Result<Result<Vec<u8>>> {
+    let flags = rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    let db = rusqlite::Connection::open_with_flags(file, flags)?;
+    let integrity: String = db.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if integrity != "ok" {
+        bail!("integrity check failed: {integrity}");
+    }
+    Ok(crate::load_settings(&db))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::{backup_time, select_pruned, Group, Policy};
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn group(stem: &str, age_days: u32) -> Group {
+        Group {
+            stem: stem.to_owned(),
+            time: backup_time(stem, "settings.db").map(str::to_owned),
+            modified: SystemTime::UNIX_EPOCH + DAY * (1000 - age_days),
+            files: Vec::new(),
+        }
+    }
+
+    fn pruned(groups: &[Group], policy: Policy) -> Vec<&str> {
+        let now = SystemTime::UNIX_EPOCH + DAY * 1000;
+        select_pruned(groups, "settings.db.daily.", &policy, now).into_iter().map(|group| group.stem.as_str()).collect()
+    }
+
+    #[test]
+    fn time_is_parsed_from_name() {
+        assert_eq!(backup_time("settings.db.2024-05-01_12-30-00", "settings.db"), Some("2024-05-01_12-30-00"));
+        assert_eq!(backup_time("settings.db.2024-05-01_12-30-00-1", "settings.db"), Some("2024-05-01_12-30-00-1"));
+        assert_eq!(backup_time("settings.db.daily.2024-05-01", "settings.db"), Some("2024-05-01"));
+        assert_eq!(backup_time("settings.db.1", "settings.db"), None);
+        assert_eq!(backup_time("other.db.2024-05-01_12-30-00", "settings.db"), None);
+    }
+
+    #[test]
+    fn count_limit_applies_to_timestamped_backups_only() {
+        let groups = [
+            group("settings.db.2024-05-03_10-00-00", 0),
+            group("settings.db.daily.2024-05-03", 0),
+            group("settings.db.2024-05-02_10-00-00", 1),
+            group("settings.db.2024-05-01_10-00-00", 2),
+        ];
+        let policy = Policy { keep: Some(2), ..Policy::default() };
+        assert_eq!(pruned(&groups, policy), ["settings.db.2024-05-01_10-00-00"]);
+    }
+
+    #[test]
+    fn old_backups_are_pruned_but_newest_one() {
+        let groups = [
+            group("settings.db.2024-05-03_10-00-00", 40),
+            group("settings.db.daily.2024-05-03", 40),
+            group("settings.db.2024-05-02_10-00-00", 10),
+        ];
+        let policy = Policy { max_age_days: Some(30), ..Policy::default() };
+        assert_eq!(pruned(&groups, policy), ["settings.db.daily.2024-05-03"]);
+    }
+}
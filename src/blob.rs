@@ -0,0 +1,98 @@
+//! Some databases contain settings which aren't pure JSON, probably leftovers
+//! of migrations from older Options+ versions. Known wrappers are stripped on
+//! load and restored on save, so Options+ gets data in the form it expects.
+
+use serde::de::IgnoredAny;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Wrapper {
+    /// 4-byte little-endian length of JSON precedes it.
+    LengthPrefixLe,
+    /// 4-byte big-endian length of JSON precedes it.
+    LengthPrefixBe,
+    /// There are some bytes after JSON document, e.g. NUL terminator.
+    Trailing(Vec<u8>),
+}
+
+impl Wrapper {
+    pub fn describe(&self) -> String {
+        match self {
+            Wrapper::LengthPrefixLe => "JSON is preceded by 4-byte little-endian length".to_owned(),
+            Wrapper::LengthPrefixBe => "JSON is preceded by 4-byte big-endian length".to_owned(),
+            Wrapper::Trailing(bytes) => format!("JSON is followed by {} extra byte(s): {:02x?}",
+                bytes.len(), &bytes[..bytes.len().min(8)]),
+        }
+    }
+}
+
+/// Detects wrapper and returns JSON inside of it. Settings written by other
+/// tools may start with BOM or whitespace, they are skipped.
+pub fn unwrap(blob: &[u8]) -> (Option<Wrapper>, &[u8]) {
+    if blob.len() > 4 && blob[4] == b'{' {
+        let prefix: [u8; 4] = blob[..4].try_into().unwrap();
+        let len = blob.len() - 4;
+        if u32::from_le_bytes(prefix) as usize == len {
+            return (Some(Wrapper::LengthPrefixLe), &blob[4..]);
+        }
+        if u32::from_be_bytes(prefix) as usize == len {
+            return (Some(Wrapper::LengthPrefixBe), &blob[4..]);
+        }
+    }
+
+    // Length prefix may look like whitespace, so it is checked first.
+    let blob = blob.strip_prefix(b"\xef\xbb\xbf").unwrap_or(blob).trim_ascii_start();
+    let mut values = serde_json::Deserializer::from_slice(blob).into_iter::<IgnoredAny>();
+    if let Some(Ok(_)) = values.next() {
+        let (json, trailing) = blob.split_at(values.byte_offset());
+        if !trailing.iter().all(u8::is_ascii_whitespace) {
+            return (Some(Wrapper::Trailing(trailing.to_vec())), json);
+        }
+    }
+
+    (None, blob)
+}
+
+/// Wraps JSON the same way as it was.
+pub fn wrap(wrapper: Option<&Wrapper>, json: &[u8]) -> Vec<u8> {
+    match wrapper {
+        None => json.to_vec(),
+        Some(Wrapper::LengthPrefixLe) => [&(json.len() as u32).to_le_bytes(), json].concat(),
+        Some(Wrapper::LengthPrefixBe) => [&(json.len() as u32).to_be_bytes(), json].concat(),
+        Some(Wrapper::Trailing(bytes)) => [json, bytes].concat(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{unwrap, wrap, Wrapper};
+
+    const JSON: &[u8] = br#"{"a": [1, 2]}"#;
+
+    #[test]
+    fn bom_and_leading_whitespace_are_skipped() {
+        assert_eq!(unwrap(b"\xef\xbb\xbf\n {}"), (None, &b"{}"[..]));
+        assert_eq!(unwrap(b" {}\0"), (Some(Wrapper::Trailing(b"\0".to_vec())), &b"{}"[..]));
+    }
+
+    #[test]
+    fn wrapped_json_is_restored_byte_for_byte() {
+        let wrappers = [None, Some(Wrapper::LengthPrefixLe), Some(Wrapper::LengthPrefixBe),
+                        Some(Wrapper::Trailing(b"\0\x01".to_vec()))];
+        for wrapper in wrappers {
+            let blob = wrap(wrapper.as_ref(), JSON);
+            assert_eq!(unwrap(&blob), (wrapper.clone(), JSON), "{wrapper:?}");
+        }
+    }
+
+    #[test]
+    fn length_prefix_must_match_json_length() {
+        let blob = [&[0, 0, 0, 1][..], JSON].concat();
+        assert_eq!(unwrap(&blob), (None, &blob[..]));
+    }
+
+    #[test]
+    fn trailing_whitespace_isnt_wrapper() {
+        let blob = [JSON, b"\n\n"].concat();
+        assert_eq!(unwrap(&blob), (None, &blob[..]));
+    }
+}
@@ -0,0 +1,92 @@
+//! Physical buttons of devices. Assignments refer buttons by slot suffix,
+//! like `c83`, which is Logitech control id of button.
+
+use std::collections::BTreeSet;
+
+use eyre::Result;
+
+use crate::{actions, config, devices::base_model, models::Settings, options::Buttons, resolve};
+
+/// Control ids are the same for all Logitech devices.
+const CONTROLS: &[(&str, &str)] = &[
+    ("c82", "middle button"),
+    ("c83", "back"),
+    ("c86", "forward"),
+    ("c91", "scroll wheel left tilt"),
+    ("c93", "scroll wheel right tilt"),
+    ("c195", "gesture button"),
+    ("c196", "mode shift button"),
+];
+
+/// Buttons which may be reassigned, by device model.
+const MODELS: &[(&str, &[&str])] = &[
+    // MX Master 3
+    ("6b023", &["c82", "c83", "c86", "c195", "c196"]),
+    // MX Master 3S
+    ("2b034", &["c82", "c83", "c86", "c195", "c196"]),
+    // M336 / M337 / M535
+    ("1b016", &["c82", "c83", "c86", "c91", "c93"]),
+];
+
+/// Returns slot suffixes of device model buttons with their names.
+/// Buttons from configuration are added to built-in ones or rename them.
+pub fn buttons(model: &str) -> Vec<(String, String)> {
+    let mut buttons: Vec<(String, String)> = MODELS.iter()
+        .filter(|(m, _)| *m == model)
+        .flat_map(|(_, suffixes)| suffixes.iter())
+        .map(|suffix| {
+            let name = CONTROLS.iter().find(|(s, _)| s == suffix).map_or(*suffix, |(_, name)| name);
+            (suffix.to_string(), name.to_owned())
+        })
+        .collect();
+    for (suffix, name) in config::get().buttons.get(model).into_iter().flatten() {
+        match buttons.iter_mut().find(|(s, _)| s == suffix) {
+            Some((_, existing)) => existing.clone_from(name),
+            None => buttons.push((suffix.clone(), name.clone())),
+        }
+    }
+    buttons
+}
+
+/// Returns buttons of device, none if its record is gone and model is unknown.
+pub fn device_buttons(settings: &Settings, slot_prefix: &str) -> Vec<(String, String)> {
+    settings.device(slot_prefix).map_or_else(Vec::new, |device| buttons(base_model(device)))
+}
+
+pub fn list(opts: Buttons, settings: Vec<u8>) -> Result<()> {
+    let settings: Settings = serde_json::from_slice(&settings)?;
+    let device = resolve::device_record(&settings, &opts.device)?;
+    let model = base_model(device);
+    let buttons = buttons(model);
+    let assigned = |suffix: &str| {
+        let slot_id = format!("{}_{suffix}", opts.device);
+        settings.default_profile()
+            .and_then(|profile| profile.assignments.iter().find(|a| a.slot_id == slot_id))
+            .map(|a| actions::describe_assignment(a, &settings))
+    };
+
+    if buttons.is_empty() {
+        println!("Buttons of {} (model {model}) are unknown, they may be added to configuration:", settings.model_name(device));
+        println!("  [buttons.{model}]\n  c82 = \"middle button\"");
+    }
+    for (suffix, name) in &buttons {
+        match assigned(suffix) {
+            Some(action) => println!("{suffix:<12} {name:<24} {action}"),
+            None => println!("{suffix:<12} {name}"),
+        }
+    }
+
+    // Assignments may exist for buttons missing in table, show them too.
+    let unknown: Vec<&str> = settings.profiles.values()
+        .flat_map(|profile| &profile.assignments)
+        .filter(|a| a.device() == Some(opts.device.as_str()))
+        .map(|a| &a.slot_id[opts.device.len() + 1..])
+        .filter(|suffix| !buttons.iter().any(|(s, _)| s == suffix))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    if !unknown.is_empty() {
+        println!("Assigned, but unknown: {}", unknown.join(", "));
+    }
+    Ok(())
+}
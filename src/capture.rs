@@ -0,0 +1,141 @@
+//! Capture of everything configured for one device into file, to restore it
+//! when the device is re-paired and Options+ gives it another slot prefix.
+
+use std::{collections::HashMap, path::Path};
+
+use eyre::{bail, eyre, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    devices::base_model,
+    models::{self, new_profile_key, Assignment, Profile, Settings, SmartAction},
+    options::{Capture, Restore},
+    resolve, smart_actions,
+};
+
+#[derive(Deserialize, Serialize)]
+struct Captured {
+    /// Slot prefix device had when captured.
+    device: String,
+    /// Base model of device, unknown if device record is already removed.
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    model: Option<String>,
+    profiles: Vec<CapturedProfile>,
+    /// Smart Actions bound to device.
+    #[serde(default, skip_serializing_if="Vec::is_empty")]
+    smart_actions: Vec<SmartAction>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct CapturedProfile {
+    #[serde(rename="applicationId", default, skip_serializing_if="Option::is_none")]
+    application_id: Option<String>,
+    #[serde(default, skip_serializing_if="Vec::is_empty")]
+    assignments: Vec<Assignment>,
+    /// Device settings, keys are without slot prefix.
+    #[serde(default, skip_serializing_if="Map::is_empty")]
+    settings: Map<String, Value>,
+}
+
+pub fn capture(opts: Capture, settings: Vec<u8>) -> Result<()> {
+    let settings: Settings = serde_json::from_slice(&settings)?;
+    // Device record may be gone after unpairing, its assignments are still there.
+    let device = resolve::device(&settings, &opts.device)?;
+    let model = settings.device(device).map(|d| base_model(d).to_owned());
+
+    let profiles: Vec<CapturedProfile> = settings.profile_keys.iter()
+        .filter_map(|key| settings.profiles.get(key))
+        .map(|profile| CapturedProfile {
+            application_id: profile.application_id.clone(),
+            assignments: profile.assignments.iter().filter(|a| a.device() == Some(device)).cloned().collect(),
+            settings: profile.device_settings(device).map(|(setting, value)| (setting.to_owned(), value.clone())).collect(),
+        })
+        .filter(|profile| !profile.assignments.is_empty() || !profile.settings.is_empty())
+        .collect();
+    let smart_actions = settings.smart_actions.iter().flatten()
+        .filter(|action| smart_actions::is_bound_to(action, device))
+        .cloned()
+        .collect();
+    let captured = Captured { device: device.to_owned(), model, profiles, smart_actions };
+    crate::write_output(opts.output.as_deref(), &serde_json::to_string_pretty(&captured)?)
+}
+
+pub fn restore(db_path: &Path, opts: Restore, db: rusqlite::Connection, old_settings: Vec<u8>) -> Result<()> {
+    let mut settings: Settings = serde_json::from_slice(&old_settings)?;
+    let target = resolve::device_record(&settings, &opts.device)?;
+    let to = target.slot_prefix.clone();
+
+    let text = std::fs::read_to_string(&opts.file).map_err(|err| eyre!("can't read {}: {err}", opts.file.display()))?;
+    let captured: Captured = serde_json::from_str(&text)?;
+    if let Some(model) = captured.model.as_ref().filter(|model| model.as_str() != base_model(target)) {
+        bail!("{} is captured from {model} device, but {to} is {}", opts.file.display(), settings.model_name(target));
+    }
+
+    // Slot ids may be referred anywhere, in assignments and Smart Actions alike.
+    let from = captured.device.clone();
+    let mut captured = serde_json::to_value(captured)?;
+    models::rewrite_strings(&mut captured, &mut |s| smart_actions::rewrite_slot(s, &from, &to));
+    let captured: Captured = serde_json::from_value(captured)?;
+
+    // Everything target device had is replaced, in profiles missing in capture too,
+    // otherwise their assignments would refer removed Smart Actions.
+    for profile in settings.profiles.values_mut() {
+        profile.assignments.retain(|a| a.device() != Some(to.as_str()));
+        let old: Vec<String> = profile.device_settings(&to).map(|(setting, _)| format!("{to}_{setting}")).collect();
+        for key in old {
+            profile.rest.shift_remove(&key);
+        }
+    }
+    let ids = restore_smart_actions(&mut settings, captured.smart_actions, &to);
+    for captured in captured.profiles {
+        let key = profile_key(&mut settings, captured.application_id);
+        let profile = settings.profiles.get_mut(&key).unwrap();
+        for mut assignment in captured.assignments {
+            smart_actions::rewrite_references(&mut assignment, &ids);
+            profile.assignments.push(assignment);
+        }
+        for (setting, value) in captured.settings {
+            profile.rest.insert(format!("{to}_{setting}"), value);
+        }
+        println!("restored {} profile", profile.display_name());
+    }
+    crate::write_settings(db_path, &db, &old_settings, &settings, opts.dry_run)
+}
+
+/// Replaces Smart Actions bound to device with restored ones. They get new
+/// ids, because originals may still exist. Returns mapping from captured ids
+/// to new ones.
+fn restore_smart_actions(settings: &mut Settings, restored: Vec<SmartAction>, device: &str) -> HashMap<String, String> {
+    let mut ids = HashMap::new();
+    if restored.is_empty() && settings.smart_actions.is_none() {
+        return ids;
+    }
+    let actions = settings.smart_actions.get_or_insert_with(Vec::new);
+    actions.retain(|action| !smart_actions::is_bound_to(action, device));
+    for mut action in restored {
+        let id = uuid::Uuid::new_v4().to_string();
+        ids.insert(action.id.clone(), id.clone());
+        action.id = id;
+        if actions.iter().any(|a| a.name == action.name) {
+            action.name = format!("{} ({device})", action.name);
+        }
+        actions.push(action);
+    }
+    ids
+}
+
+/// Returns key of profile for application, creating it if needed.
+fn profile_key(settings: &mut Settings, application_id: Option<String>) -> String {
+    let existing = settings.profiles.iter()
+        .find(|(_, profile)| match application_id.as_deref() {
+            Some("") | None => profile.is_default(),
+            app => profile.application_id.as_deref() == app,
+        })
+        .map(|(key, _)| key.clone());
+    existing.unwrap_or_else(|| {
+        let key = new_profile_key();
+        settings.add_profile(key.clone(), Profile { application_id, name: None, assignments: Vec::new(), rest: Map::new() });
+        key
+    })
+}
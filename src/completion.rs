@@ -0,0 +1,56 @@
+use std::io::Write;
+
+use clap::CommandFactory;
+use clap_complete::{engine::CompletionCandidate, env::Shells, CompleteEnv};
+use eyre::{eyre, Result};
+
+use crate::{models::Settings, options::Options};
+
+const BIN: &str = "logi-man";
+
+/// Answers completion request if shell made one, exits in this case.
+pub fn complete() {
+    CompleteEnv::with_factory(Options::command).bin(BIN).complete();
+}
+
+/// Prints shell script which registers completions.
+///
+/// Script doesn't contain completions themselves, it calls back into logi-man,
+/// so it can complete real device ids from database.
+pub fn completions(shell: &str) -> Result<()> {
+    let shells = Shells::builtins();
+    let completer = shells.completer(shell).ok_or_else(|| eyre!(
+        "unsupported shell '{shell}', expected one of: {}", shells.names().collect::<Vec<_>>().join(", ")))?;
+    let mut stdout = std::io::stdout();
+    completer.write_registration("COMPLETE", BIN, BIN, BIN, &mut stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Loads settings from default database for completion.
+///
+/// Database path given on command line isn't available to completer,
+/// so the one from environment or configuration is used.
+fn load_settings() -> Option<Settings> {
+    crate::config::init().ok()?;
+    let given = std::env::var_os(crate::options::DB_ENV).map(std::path::PathBuf::from);
+    let db = crate::open_database(&crate::db_path(given).ok()?, true).ok()?;
+    let settings = crate::load_settings(&db).ok()?;
+    serde_json::from_slice(&settings).ok()
+}
+
+pub fn complete_devices() -> Vec<CompletionCandidate> {
+    let Some(settings) = load_settings() else { return Vec::new() };
+    settings.slot_prefixes().into_iter()
+        .map(|device| CompletionCandidate::new(device.to_owned())
+            .help(settings.device(device).map(|device| settings.model_name(device).to_owned().into())))
+        .collect()
+}
+
+pub fn complete_profiles() -> Vec<CompletionCandidate> {
+    let Some(settings) = load_settings() else { return Vec::new() };
+    settings.profiles.values()
+        .map(|profile| CompletionCandidate::new(profile.display_name().to_owned())
+            .help(profile.name.clone().map(Into::into)))
+        .collect()
+}
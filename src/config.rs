@@ -0,0 +1,111 @@
+//! Optional logi-man configuration, `logi-man/config.toml` in user
+//! configuration directory.
+
+use std::{collections::BTreeMap, path::{Path, PathBuf}, sync::OnceLock};
+
+use directories_next::BaseDirs;
+use eyre::{ensure, eyre, OptionExt, Result};
+use serde::Deserialize;
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Settings database used when `--db` isn't given, `~` is expanded
+    /// to home directory.
+    pub db: Option<String>,
+    /// Take additional snapshot of database before the first change of the day.
+    pub daily_snapshot: bool,
+    pub json_backup: JsonBackup,
+    pub backups: Backups,
+    /// Databases logi-man must not change: directories containing them,
+    /// files or glob patterns, `~` is expanded to home directory.
+    pub protected_paths: Vec<String>,
+    /// Additional buttons by device model: slot suffix to button name.
+    pub buttons: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// Backup of settings JSON only, it is much smaller than database copy.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JsonBackup {
+    /// Save settings JSON next to every database backup.
+    pub enabled: bool,
+    /// Sort keys and strip whitespace, so same settings give same file.
+    pub canonical: bool,
+    /// Compress with gzip.
+    pub compress: bool,
+}
+
+/// Retention and compression of backups, command line options override it.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Backups {
+    /// Keep only this many latest backups.
+    pub keep: Option<u64>,
+    /// Remove backups older than this number of days.
+    pub max_age_days: Option<u64>,
+    /// Compress database backups with gzip.
+    pub compress: bool,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Directory of logi-man configuration and presets.
+pub fn dir() -> Result<PathBuf> {
+    let dirs = BaseDirs::new().ok_or_eyre("can't get user directory path")?;
+    Ok(dirs.config_dir().join("logi-man"))
+}
+
+pub fn path() -> Result<PathBuf> {
+    Ok(dir()?.join("config.toml"))
+}
+
+/// Loads configuration, missing file means default one.
+pub fn init() -> Result<()> {
+    let path = path()?;
+    let config = match std::fs::read_to_string(&path) {
+        Ok(text) => toml::from_str(&text).map_err(|err| eyre!("invalid configuration {}: {err}", path.display()))?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Config::default(),
+        Err(err) => return Err(err.into()),
+    };
+    let backups: &Backups = &config.backups;
+    for (key, value) in [("keep", backups.keep), ("max_age_days", backups.max_age_days)] {
+        ensure!(value != Some(0), "invalid configuration {}: backups.{key} must be positive", path.display());
+    }
+    let _ = CONFIG.set(config);
+    Ok(())
+}
+
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// Returns configured database path.
+pub fn db() -> Option<PathBuf> {
+    get().db.as_deref().map(expand_home)
+}
+
+/// Returns protection rule matching path, if any.
+pub fn protecting_rule(path: &Path) -> Option<&'static str> {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    get().protected_paths.iter()
+        .find(|rule| {
+            let rule = expand_home(rule);
+            path.starts_with(&rule)
+                || glob::Pattern::new(&rule.to_string_lossy()).is_ok_and(|pattern| pattern.matches_path_with(&path, MATCH_OPTIONS))
+        })
+        .map(String::as_str)
+}
+
+const MATCH_OPTIONS: glob::MatchOptions = glob::MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+fn expand_home(rule: &str) -> PathBuf {
+    match (rule.strip_prefix("~/"), BaseDirs::new()) {
+        (Some(rest), Some(dirs)) => dirs.home_dir().join(rest),
+        _ => PathBuf::from(rule),
+    }
+}
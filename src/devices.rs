@@ -0,0 +1,352 @@
+use std::{collections::BTreeSet, path::Path};
+
+use eyre::{ensure, Result};
+use serde_json::Value;
+
+use crate::{
+    actions, buttons,
+    models::{Assignment, ConnectedDevice, Settings, DEFAULT_PROFILE_NAME},
+    options::{CompareDevices, ForgetDevice, Repair},
+    resolve,
+};
+
+/// Finds devices without assignments and suggests where to copy them from.
+///
+/// It is what usually happens after mouse is replaced: new one is paired
+/// and has default settings, while old one has everything configured.
+pub fn suggest_transfer(settings: Vec<u8>) -> Result<()> {
+    let settings: Settings = serde_json::from_slice(&settings)?;
+    let devices = settings.known_devices();
+
+    let mut suggested = false;
+    for target in devices.iter().filter(|device| settings.assignment_count(&device.slot_prefix) == 0) {
+        let Some((source, count)) = suggest_source(&settings, target) else { continue };
+
+        println!("{} ({}) has no assignments, {} ({}) has {}:",
+            target.slot_prefix, settings.model_name(target), source.slot_prefix, settings.model_name(source), count);
+        println!("  logi-man transfer-assignments {} {}", source.slot_prefix, target.slot_prefix);
+        suggested = true;
+    }
+
+    if !suggested {
+        println!("Nothing to suggest, all devices have assignments");
+    }
+    Ok(())
+}
+
+/// Finds device to copy assignments from to target one, returns it
+/// with number of its assignments.
+pub fn suggest_source<'a>(settings: &'a Settings, target: &ConnectedDevice) -> Option<(&'a ConnectedDevice, usize)> {
+    // Devices are appended to the list when connected first time,
+    // so position tells which device is newer.
+    let age = |device: &ConnectedDevice| settings.ever_connected_devices.devices.iter()
+        .position(|d| d.slot_prefix == device.slot_prefix);
+    settings.known_devices().into_iter()
+        .filter(|device| device.slot_prefix != target.slot_prefix)
+        .map(|device| (device, settings.assignment_count(&device.slot_prefix)))
+        .filter(|(_, count)| *count > 0)
+        .max_by_key(|(device, count)| (similarity(settings, device, target), age(device) < age(target), *count))
+}
+
+/// Rates how likely devices are to have the same buttons.
+fn similarity(settings: &Settings, a: &ConnectedDevice, b: &ConnectedDevice) -> u8 {
+    let family = |device: &ConnectedDevice| settings.model_name(device).split_whitespace().next().map(str::to_owned);
+    if base_model(a) == base_model(b) || settings.model_name(a) == settings.model_name(b) {
+        2
+    } else if family(a) == family(b) {
+        1
+    } else {
+        0
+    }
+}
+
+/// Shows devices side by side and previews transfer of assignments from
+/// the first one to the second.
+pub fn compare(opts: CompareDevices, settings: Vec<u8>) -> Result<()> {
+    let settings: Settings = serde_json::from_slice(&settings)?;
+    let a = resolve::device(&settings, &opts.a)?;
+    let b = resolve::device(&settings, &opts.b)?;
+    let (buttons_a, buttons_b) = (buttons::device_buttons(&settings, a), buttons::device_buttons(&settings, b));
+    let (assigned_a, assigned_b) = (assignments(&settings, a), assignments(&settings, b));
+    // Record of device may be gone, while its assignments are left.
+    let model_name = |device: &str| settings.device(device).map_or("unknown", |device| settings.model_name(device));
+    // Buttons of models missing in catalog are unknown, assume device has any.
+    let has_button = |buttons: &[(String, String)], suffix: &str| buttons.is_empty() || buttons.iter().any(|(s, _)| s == suffix);
+    let label = |suffix: &str| match buttons_b.iter().chain(&buttons_a).find(|(s, _)| s == suffix) {
+        Some((_, name)) => format!("{suffix} ({name})"),
+        None => suffix.to_owned(),
+    };
+
+    println!("{:<28} {:<32} {}", "", a, b);
+    println!("{:<28} {:<32} {}", "model", model_name(a), model_name(b));
+    println!("{:<28} {:<32} {}", "assignments", assigned_a.len(), assigned_b.len());
+
+    let mut suffixes: Vec<&str> = buttons_a.iter().chain(&buttons_b).map(|(s, _)| s.as_str())
+        .chain(assigned_a.iter().chain(&assigned_b).map(|(_, suffix, _)| *suffix))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    // Order by control id, so that "c195" goes after "c82".
+    suffixes.sort_by_key(|suffix| (suffix.trim_start_matches('c').parse::<u32>().unwrap_or(u32::MAX), *suffix));
+    // Action in default profile and number of application profiles button is assigned in.
+    let cell = |assigned: &[(&str, &str, &Assignment)], buttons: &[(String, String)], suffix: &str| {
+        let is_default = |profile: &str| profile == DEFAULT_PROFILE_NAME;
+        let default = assigned.iter()
+            .find(|(profile, s, _)| *s == suffix && is_default(profile))
+            .map(|(_, _, a)| actions::describe_assignment(a, &settings));
+        let in_apps = assigned.iter().filter(|(profile, s, _)| *s == suffix && !is_default(profile)).count();
+        let mut cell = match default {
+            Some(action) => action,
+            None if has_button(buttons, suffix) => "default".to_owned(),
+            None => "no button".to_owned(),
+        };
+        if in_apps > 0 {
+            cell.push_str(&format!(" (+{in_apps} in app profiles)"));
+        }
+        cell
+    };
+    println!("\nButtons:");
+    for suffix in &suffixes {
+        println!("{:<28} {:<32} {}", label(suffix), cell(&assigned_a, &buttons_a, suffix), cell(&assigned_b, &buttons_b, suffix));
+    }
+
+    let (settings_a, settings_b) = (setting_names(&settings, a), setting_names(&settings, b));
+    println!("\nSettings:");
+    let only_a: Vec<&str> = settings_a.difference(&settings_b).copied().collect();
+    let only_b: Vec<&str> = settings_b.difference(&settings_a).copied().collect();
+    println!("  only on {}: {}", a, if only_a.is_empty() { "none".to_owned() } else { only_a.join(", ") });
+    println!("  only on {}: {}", b, if only_b.is_empty() { "none".to_owned() } else { only_b.join(", ") });
+
+    // Transfer replaces all assignments of target device with source ones.
+    // It copies assignments for buttons target lacks too, they just do nothing.
+    println!("\nTransfer from {} to {}:", a, b);
+    let mut lost = false;
+    for (profile, suffix, assignment) in &assigned_a {
+        if !has_button(&buttons_b, suffix) {
+            println!("  ineffective: {profile} {}: {}, copied to button target lacks", label(suffix),
+                actions::describe_assignment(assignment, &settings));
+            lost = true;
+        }
+    }
+    for (profile, suffix, assignment) in &assigned_b {
+        if !assigned_a.iter().any(|(p, s, _)| p == profile && s == suffix) {
+            println!("  removed: {profile} {}: {}", label(suffix), actions::describe_assignment(assignment, &settings));
+            lost = true;
+        }
+    }
+    for (suffix, _) in &buttons_b {
+        if !assigned_a.iter().any(|(_, s, _)| s == suffix) {
+            println!("  left default: {}", label(suffix));
+        }
+    }
+    if !lost {
+        println!("  no assignments are lost");
+    }
+    Ok(())
+}
+
+/// Returns names of device settings overridden in any profile.
+fn setting_names<'a>(settings: &'a Settings, device: &'a str) -> BTreeSet<&'a str> {
+    settings.profiles.values()
+        .flat_map(|profile| profile.device_settings(device).map(|(setting, _)| setting))
+        .collect()
+}
+
+/// Returns assignments of device as profile name, slot suffix and
+/// assignment, in order of profiles.
+fn assignments<'a>(settings: &'a Settings, device: &str) -> Vec<(&'a str, &'a str, &'a Assignment)> {
+    settings.profile_keys.iter()
+        .filter_map(|key| settings.profiles.get(key))
+        .flat_map(|profile| profile.assignments.iter().map(move |a| (profile.display_name(), a)))
+        .filter_map(|(profile, a)| {
+            let (d, suffix) = a.slot_id.split_once('_')?;
+            (d == device).then_some((profile, suffix, a))
+        })
+        .collect()
+}
+
+pub fn repair(db_path: &Path, opts: Repair, db: rusqlite::Connection, old_settings: Vec<u8>) -> Result<()> {
+    ensure!(opts.dedupe_devices || opts.remove_orphaned_assignments,
+        "nothing to do, use --dedupe-devices and/or --remove-orphaned-assignments");
+    let mut settings: Settings = serde_json::from_slice(&old_settings)?;
+    let mut repaired = false;
+
+    if opts.dedupe_devices {
+        // Keep the last record of every device, it is the one which is used
+        // when devices are listed.
+        let devices = &mut settings.ever_connected_devices.devices;
+        let mut seen = BTreeSet::new();
+        let mut keep: Vec<bool> = devices.iter().rev().map(|device| seen.insert(device.slot_prefix.clone())).collect();
+        keep.reverse();
+        for (device, keep) in devices.iter().zip(&keep) {
+            if !keep {
+                println!("removed duplicate record of device {}", device.slot_prefix);
+                repaired = true;
+            }
+        }
+        let mut keep = keep.into_iter();
+        devices.retain(|_| keep.next().unwrap());
+    }
+
+    if opts.remove_orphaned_assignments {
+        let known: BTreeSet<String> = settings.ever_connected_devices.devices.iter()
+            .map(|device| device.slot_prefix.clone())
+            .collect();
+        for profile in settings.profiles.values_mut() {
+            let name = profile.display_name().to_owned();
+            profile.assignments.retain(|assignment| {
+                let orphaned = assignment.device().is_none_or(|device| !known.contains(device));
+                if orphaned {
+                    println!("removed assignment {} from profile {name}", assignment.slot_id);
+                    repaired = true;
+                }
+                !orphaned
+            });
+        }
+    }
+
+    if !repaired {
+        println!("Nothing to repair");
+        return Ok(());
+    }
+    if opts.dry_run {
+        return Ok(());
+    }
+    crate::write_settings(db_path, &db, &old_settings, &settings, false)
+}
+
+pub fn forget_device(db_path: &Path, opts: ForgetDevice, db: rusqlite::Connection, old_settings: Vec<u8>) -> Result<()> {
+    let mut settings: Settings = serde_json::from_slice(&old_settings)?;
+
+    let filtered = opts.device_type.is_some() || opts.unused || opts.older_than.is_some();
+    if let (Some(device), false) = (&opts.device, filtered) {
+        if !forget(&mut settings, device, opts.migration_report) {
+            let known = settings.ever_connected_devices.devices.iter().map(|d| d.slot_prefix.as_str());
+            return Err(crate::resolve::not_found("device", device, known, "see `list-devices`"));
+        }
+    } else {
+        let candidates = select_devices(&settings, &db, &opts)?;
+        if candidates.is_empty() {
+            println!("No devices match");
+            return Ok(());
+        }
+        println!("Devices to forget:");
+        for device in &candidates {
+            println!("  {}: {} ({})", device.slot_prefix, settings.model_name(device), device.device_type);
+        }
+        let candidates: Vec<String> = candidates.into_iter().map(|device| device.slot_prefix.clone()).collect();
+        if !opts.dry_run && !opts.yes && !crate::confirm(&format!("Forget {} device(s)?", candidates.len()))? {
+            return Ok(());
+        }
+        for device in &candidates {
+            forget(&mut settings, device, opts.migration_report);
+        }
+    }
+
+    if opts.dry_run {
+        return Ok(());
+    }
+    crate::write_settings(db_path, &db, &old_settings, &settings, false)
+}
+
+/// Returns devices matching all filters, deduplicated by slot prefix.
+fn select_devices<'a>(settings: &'a Settings, db: &rusqlite::Connection, opts: &ForgetDevice) -> Result<Vec<&'a ConnectedDevice>> {
+    let now: i64 = db.query_row("SELECT unixepoch('now')", [], |row| row.get(0))?;
+    let mut seen = BTreeSet::new();
+    let mut selected = Vec::new();
+    for device in &settings.ever_connected_devices.devices {
+        if !seen.insert(&device.slot_prefix) {
+            continue;
+        }
+        if opts.device.as_ref().is_some_and(|d| *d != device.slot_prefix) {
+            continue;
+        }
+        if opts.device_type.as_ref().is_some_and(|t| !t.eq_ignore_ascii_case(&device.device_type)) {
+            continue;
+        }
+        if opts.unused && !is_unused(settings, &device.slot_prefix) {
+            continue;
+        }
+        if let Some(age) = opts.older_than {
+            // Devices with unknown connection time are never selected by age.
+            let Some(last_connected) = last_connected(db, device)? else { continue };
+            if now - last_connected < age as i64 {
+                continue;
+            }
+        }
+        selected.push(device);
+    }
+    Ok(selected)
+}
+
+fn is_unused(settings: &Settings, device: &str) -> bool {
+    settings.assignment_count(device) == 0
+        && settings.profiles.values().all(|profile| profile.device_settings(device).next().is_none())
+}
+
+/// Returns time device was connected last time as Unix time.
+fn last_connected(db: &rusqlite::Connection, device: &ConnectedDevice) -> Result<Option<i64>> {
+    Ok(match &device.last_connected {
+        Some(Value::Number(time)) => time.as_i64().map(|time| {
+            // Milliseconds are used by JavaScript, and Options+ UI is written in it.
+            if time > 100_000_000_000 { time / 1000 } else { time }
+        }),
+        Some(Value::String(time)) => db.query_row("SELECT unixepoch(?1)", [time], |row| row.get(0))?,
+        _ => None,
+    })
+}
+
+/// Removes every trace of device from settings, reporting what is removed.
+/// Returns whether anything is removed.
+fn forget(settings: &mut Settings, device: &str, migration_report: bool) -> bool {
+    let mut forgotten = false;
+
+    let records = &mut settings.ever_connected_devices.devices;
+    let models: BTreeSet<String> = records.iter()
+        .filter(|record| record.slot_prefix == device)
+        .map(|record| base_model(record).to_owned())
+        .collect();
+    let count = records.len();
+    records.retain(|record| record.slot_prefix != device);
+    if records.len() != count {
+        println!("removed {} record(s) of device {device}", count - records.len());
+        forgotten = true;
+    }
+
+    for profile in settings.profiles.values_mut() {
+        let name = profile.display_name().to_owned();
+        profile.assignments.retain(|assignment| {
+            let matches = assignment.device() == Some(device);
+            if matches {
+                println!("removed assignment {} from profile {name}", assignment.slot_id);
+                forgotten = true;
+            }
+            !matches
+        });
+        let keys: Vec<String> = profile.device_settings(device).map(|(setting, _)| format!("{device}_{setting}")).collect();
+        for key in keys {
+            profile.rest.shift_remove(&key);
+            println!("removed setting {key} from profile {name}");
+            forgotten = true;
+        }
+    }
+
+    if migration_report {
+        // Other devices of the same model still need model name.
+        let remaining: BTreeSet<&str> = settings.ever_connected_devices.devices.iter().map(base_model).collect();
+        settings.migration_report.devices.retain(|record| {
+            let remove = models.contains(&record.model_id) && !remaining.contains(record.model_id.as_str());
+            if remove {
+                println!("removed model {} from migration report", record.model_id);
+            }
+            !remove
+        });
+    }
+
+    forgotten
+}
+
+/// Device model without suffix, as it is used in migration report.
+pub fn base_model(device: &ConnectedDevice) -> &str {
+    device.device_model.split('_').next().unwrap_or_default()
+}
@@ -0,0 +1,113 @@
+use std::{fmt::Display, fs::OpenOptions, io::ErrorKind, path::Path};
+
+use eyre::{bail, eyre, Result};
+
+use crate::models::Settings;
+
+/// Turns failure to open database into error user can act on.
+///
+/// SQLite reports just "unable to open database file" no matter what
+/// happened, so look at the file ourselves to find out the real reason.
+pub fn explain_open_error(db_path: &Path, err: rusqlite::Error) -> eyre::Report {
+    match OpenOptions::new().read(true).write(true).open(db_path) {
+        Err(io_err) if io_err.kind() == ErrorKind::NotFound => eyre!(
+            "settings database not found at {}; is Logi Options+ installed?", db_path.display()),
+        Err(io_err) if io_err.kind() == ErrorKind::PermissionDenied => eyre!(
+            "permission denied opening {}\n\n{}", db_path.display(), permission_guidance()),
+        _ => eyre::Report::new(err).wrap_err(format!("can't open database {}", db_path.display())),
+    }
+}
+
+fn permission_guidance() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "macOS protects application data in ~/Library from other applications. \
+         Grant Full Disk Access to your terminal application in \
+         System Settings → Privacy & Security → Full Disk Access, then restart the terminal."
+    } else {
+        "Check that current user is allowed to read and write database file and its directory."
+    }
+}
+
+/// Checks that database is accessible and contains settings logi-man understands.
+pub fn doctor(db_path: &Path) -> Result<()> {
+    let mut report = Report::default();
+    println!("Database: {}", db_path.display());
+
+    // Checks are dependent, there is no sense to continue after first failure.
+    let _ = run_checks(db_path, &mut report);
+
+    if report.problems > 0 {
+        bail!("found {} problem(s)", report.problems);
+    }
+    println!("No problems found");
+    Ok(())
+}
+
+fn run_checks(db_path: &Path, report: &mut Report) -> Result<(), ()> {
+    match std::fs::metadata(db_path) {
+        Ok(_) => report.ok("database file exists"),
+        Err(err) if err.kind() == ErrorKind::PermissionDenied =>
+            return report.fail("database file exists", permission_guidance()),
+        Err(err) => return report.fail("database file exists", err),
+    }
+
+    match OpenOptions::new().read(true).open(db_path) {
+        Ok(_) => report.ok("database file is readable"),
+        Err(err) if err.kind() == ErrorKind::PermissionDenied =>
+            return report.fail("database file is readable", permission_guidance()),
+        Err(err) => return report.fail("database file is readable", err),
+    }
+
+    match OpenOptions::new().write(true).open(db_path) {
+        Ok(_) => report.ok("database file is writable"),
+        Err(err) if err.kind() == ErrorKind::PermissionDenied =>
+            report.fail::<()>("database file is writable", permission_guidance()).unwrap_or_default(),
+        // Read-only commands may still work, so continue checking.
+        Err(err) => report.fail::<()>("database file is writable", err).unwrap_or_default(),
+    }
+
+    let db = crate::open_database(db_path, false).or_else(|err| report.fail("database can be opened", format!("{err:#}")))?;
+    report.ok("database can be opened");
+
+    let blob = crate::load_blob(&db).or_else(|err| report.fail("settings can be loaded", format!("{err:#}")))?;
+    report.ok("settings can be loaded");
+
+    let (wrapper, settings) = crate::blob::unwrap(&blob);
+    if let Some(wrapper) = wrapper {
+        report.note("settings are wrapped", format!("{}\nWrapper is preserved when settings are saved.", wrapper.describe()));
+    }
+
+    serde_json::from_slice::<Settings>(settings).or_else(|err| report.fail("settings can be parsed", err))?;
+    report.ok("settings can be parsed");
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct Report {
+    problems: usize,
+}
+
+impl Report {
+    fn ok(&mut self, check: &str) {
+        println!("[ok]   {check}");
+    }
+
+    fn note(&mut self, finding: &str, details: impl Display) {
+        println!("[note] {finding}");
+        print_details(details);
+    }
+
+    fn fail<T>(&mut self, check: &str, details: impl Display) -> Result<T, ()> {
+        println!("[FAIL] {check}");
+        print_details(details);
+        self.problems += 1;
+        Err(())
+    }
+}
+
+fn print_details(details: impl Display) {
+    for line in details.to_string().lines() {
+        println!("       {line}");
+    }
+}
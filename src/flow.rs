@@ -0,0 +1,45 @@
+use std::path::Path;
+
+use eyre::Result;
+
+use crate::{models::Settings, options::{Flow, FlowCommand}};
+
+pub fn flow(db_path: &Path, opts: Flow, db: rusqlite::Connection, old_settings: Vec<u8>) -> Result<()> {
+    let mut settings: Settings = serde_json::from_slice(&old_settings)?;
+    let Some(flow) = settings.flow.as_mut() else {
+        println!("Flow isn't configured");
+        return Ok(());
+    };
+
+    match opts.command {
+        FlowCommand::Show => {
+            println!("Computers:");
+            if flow.computers.is_empty() {
+                println!("  none");
+            }
+            for computer in &flow.computers {
+                println!("  {}: edge {}", computer.name.as_deref().unwrap_or("<unnamed>"), computer.edge.as_deref().unwrap_or("-"));
+                for (key, value) in &computer.rest {
+                    println!("    {key}: {value}");
+                }
+            }
+            if !flow.rest.is_empty() {
+                println!("Settings:");
+                for (key, value) in &flow.rest {
+                    println!("  {key}: {value}");
+                }
+            }
+            Ok(())
+        }
+        FlowCommand::Reset { dry_run } => {
+            if flow.computers.is_empty() {
+                println!("There are no paired computers");
+                return Ok(());
+            }
+            for computer in flow.computers.drain(..) {
+                println!("forgotten: {}", computer.name.as_deref().unwrap_or("<unnamed>"));
+            }
+            crate::write_settings(db_path, &db, &old_settings, &settings, dry_run)
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::{collections::BTreeMap, path::Path};
+
+use eyre::Result;
+use serde_json::Map;
+
+use crate::{
+    actions,
+    models::{Assignment, AssignmentAction, Settings},
+    options::{GestureDirection, Gestures, GesturesCommand},
+    resolve,
+};
+
+/// Slot suffix of gesture button, it's the same for all devices having one.
+const GESTURE_BUTTON: &str = "c195";
+
+/// Actions available for gestures in Options+ UI.
+const ACTIONS: &[&str] = &[
+    "none", "mission-control", "app-expose", "show-desktop", "launchpad",
+    "switch-desktop-left", "switch-desktop-right", "back", "forward",
+    "volume-up", "volume-down", "mute", "play-pause", "next-track", "previous-track",
+    "zoom-in", "zoom-out", "screen-capture", "lookup",
+];
+
+const DIRECTIONS: [GestureDirection; 5] = [
+    GestureDirection::Up, GestureDirection::Down, GestureDirection::Left,
+    GestureDirection::Right, GestureDirection::Press,
+];
+
+pub fn gestures(db_path: &Path, opts: Gestures, db: rusqlite::Connection, old_settings: Vec<u8>) -> Result<()> {
+    let mut settings: Settings = serde_json::from_slice(&old_settings)?;
+    match opts.command {
+        GesturesCommand::Show { device, profile } => {
+            resolve::device(&settings, &device)?;
+            let profile = resolve::profile(&settings, &profile)?.1;
+            let slot_id = format!("{device}_{GESTURE_BUTTON}");
+            let action = profile.assignments.iter().find(|a| a.slot_id == slot_id).map(Assignment::action);
+            let Some(AssignmentAction::Gesture { gestures }) = action else {
+                println!("Gesture button of {device} isn't configured in this profile");
+                return Ok(());
+            };
+            for direction in DIRECTIONS {
+                let action = gestures.get(direction_key(direction)).map(|action| actions::describe(action, &settings));
+                println!("{:<6} {}", direction_key(direction), action.as_deref().unwrap_or("default"));
+            }
+            Ok(())
+        }
+        GesturesCommand::Set { device, profile, direction, action, dry_run } => {
+            let action = parse_action(&action)?;
+            resolve::device(&settings, &device)?;
+            let key = resolve::profile(&settings, &profile)?.0.clone();
+            let profile = settings.profiles.get_mut(&key).unwrap();
+
+            let slot_id = format!("{device}_{GESTURE_BUTTON}");
+            let index = match profile.assignments.iter().position(|a| a.slot_id == slot_id) {
+                Some(index) => index,
+                None => {
+                    profile.assignments.push(Assignment { slot_id, rest: Map::new() });
+                    profile.assignments.len() - 1
+                }
+            };
+            let assignment = &mut profile.assignments[index];
+
+            // Button may have other kind of action assigned, replace it then.
+            let (mut gestures, configured) = match assignment.action() {
+                AssignmentAction::Gesture { gestures } => (gestures, true),
+                _ => (BTreeMap::new(), false),
+            };
+            // Direction without action falls back to default one, there
+            // is nothing to clear if button isn't configured for gestures.
+            if action == AssignmentAction::Default && !configured {
+                println!("Gesture button of {device} isn't configured in this profile");
+                return Ok(());
+            }
+            gestures.insert(direction_key(direction).to_owned(), action);
+            if gestures.values().all(|action| *action == AssignmentAction::Default) {
+                profile.assignments.remove(index);
+            } else {
+                assignment.set_action(&AssignmentAction::Gesture { gestures });
+            }
+
+            crate::write_settings(db_path, &db, &old_settings, &settings, dry_run)
+        }
+    }
+}
+
+fn direction_key(direction: GestureDirection) -> &'static str {
+    match direction {
+        GestureDirection::Up => "up",
+        GestureDirection::Down => "down",
+        GestureDirection::Left => "left",
+        GestureDirection::Right => "right",
+        GestureDirection::Press => "press",
+    }
+}
+
+/// Parses action name, one of [`ACTIONS`]. Options+ stores actions as
+/// shortcut names like "MISSION_CONTROL", none is default action.
+pub fn parse_action(name: &str) -> Result<AssignmentAction> {
+    match name {
+        _ if !ACTIONS.contains(&name) => Err(resolve::not_found("action", name, ACTIONS.iter().copied(),
+            &format!("expected one of: {}", ACTIONS.join(", ")))),
+        "none" => Ok(AssignmentAction::Default),
+        name => Ok(AssignmentAction::OsShortcut { shortcut: name.to_uppercase().replace('-', "_") }),
+    }
+}
@@ -0,0 +1,137 @@
+//! Hooks called when settings are loaded and saved.
+//!
+//! They let embedders attach logging, metrics or custom validation.
+//! logi-man itself records history of changes with a hook.
+
+use std::{path::Path, sync::RwLock};
+
+use eyre::Result;
+use serde_json::Value;
+
+/// Receives settings events, all methods do nothing by default.
+/// Settings are passed as JSON, already stripped of blob wrapper.
+pub trait Hook: Send + Sync {
+    /// Called after settings are loaded from database.
+    /// Error aborts command.
+    fn on_load(&self, _db_path: &Path, _settings: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called with differences between current and new settings before
+    /// they are saved.
+    fn on_diff(&self, _db_path: &Path, _differences: &[Difference]) {}
+
+    /// Called before settings are saved, error cancels saving.
+    fn before_save(&self, _db_path: &Path, _old: &[u8], _new: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after settings are saved and verified.
+    fn after_save(&self, _db_path: &Path, _old: &[u8], _new: &[u8]) {}
+}
+
+/// Changed JSON value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// JSON pointer to changed value.
+    pub pointer: String,
+    /// Old value, `None` if it is added.
+    pub old: Option<Value>,
+    /// New value, `None` if it is removed.
+    pub new: Option<Value>,
+}
+
+static HOOKS: RwLock<Vec<Box<dyn Hook>>> = RwLock::new(Vec::new());
+
+/// Adds hook, hooks are called in order of registration.
+pub fn register(hook: impl Hook + 'static) {
+    HOOKS.write().unwrap().push(Box::new(hook));
+}
+
+pub(crate) fn on_load(db_path: &Path, settings: &[u8]) -> Result<()> {
+    HOOKS.read().unwrap().iter().try_for_each(|hook| hook.on_load(db_path, settings))
+}
+
+pub(crate) fn before_save(db_path: &Path, old: &[u8], new: &[u8]) -> Result<()> {
+    let hooks = HOOKS.read().unwrap();
+    if !hooks.is_empty() {
+        let differences = diff(&serde_json::from_slice(old)?, &serde_json::from_slice(new)?);
+        hooks.iter().for_each(|hook| hook.on_diff(db_path, &differences));
+    }
+    hooks.iter().try_for_each(|hook| hook.before_save(db_path, old, new))
+}
+
+pub(crate) fn after_save(db_path: &Path, old: &[u8], new: &[u8]) {
+    HOOKS.read().unwrap().iter().for_each(|hook| hook.after_save(db_path, old, new));
+}
+
+/// Returns changed values. Objects are compared key by key, arrays are
+/// compared item by item if their lengths are equal, otherwise whole
+/// array is reported as changed.
+pub fn diff(old: &Value, new: &Value) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    diff_at(String::new(), old, new, &mut differences);
+    differences
+}
+
+fn diff_at(pointer: String, old: &Value, new: &Value, differences: &mut Vec<Difference>) {
+    match (old, new) {
+        _ if old == new => {}
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old {
+                let pointer = format!("{pointer}/{}", escape(key));
+                match new.get(key) {
+                    Some(new_value) => diff_at(pointer, old_value, new_value, differences),
+                    None => differences.push(Difference { pointer, old: Some(old_value.clone()), new: None }),
+                }
+            }
+            for (key, new_value) in new.iter().filter(|(key, _)| !old.contains_key(*key)) {
+                let pointer = format!("{pointer}/{}", escape(key));
+                differences.push(Difference { pointer, old: None, new: Some(new_value.clone()) });
+            }
+        }
+        (Value::Array(old), Value::Array(new)) if old.len() == new.len() => {
+            for (index, (old, new)) in old.iter().zip(new).enumerate() {
+                diff_at(format!("{pointer}/{index}"), old, new, differences);
+            }
+        }
+        _ => differences.push(Difference { pointer, old: Some(old.clone()), new: Some(new.clone()) }),
+    }
+}
+
+/// Escapes JSON pointer reference token.
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{diff, Difference};
+
+    fn difference(pointer: &str, old: Option<Value>, new: Option<Value>) -> Difference {
+        Difference { pointer: pointer.to_owned(), old, new }
+    }
+
+    #[test]
+    fn objects_are_compared_key_by_key() {
+        let old = json!({"same": 1, "changed": {"a": 1}, "removed": true, "a/b~c": 1});
+        let new = json!({"same": 1, "changed": {"a": 2}, "added": null, "a/b~c": 2});
+        assert_eq!(diff(&old, &new), [
+            difference("/changed/a", Some(json!(1)), Some(json!(2))),
+            difference("/removed", Some(json!(true)), None),
+            difference("/a~1b~0c", Some(json!(1)), Some(json!(2))),
+            difference("/added", None, Some(json!(null))),
+        ]);
+    }
+
+    #[test]
+    fn arrays_of_different_length_are_reported_whole() {
+        let (old, new) = (json!({"list": [1, 2, 3]}), json!({"list": [1, 5, 3]}));
+        assert_eq!(diff(&old, &new), [difference("/list/1", Some(json!(2)), Some(json!(5)))]);
+        let (old, new) = (json!([1, 2]), json!([1, 2, 3]));
+        assert_eq!(diff(&old, &new), [difference("", Some(old.clone()), Some(new.clone()))]);
+        assert!(diff(&new, &new).is_empty());
+    }
+}
@@ -0,0 +1,183 @@
+//! History of settings changes. logi-man records every change it makes
+//! and changes noticed by `watch` into SQLite database in user data
+//! directory.
+
+use std::path::{Path, PathBuf};
+
+use directories_next::BaseDirs;
+use eyre::{OptionExt, Result};
+use sha2::{Digest, Sha256};
+
+use crate::hooks::Hook;
+
+/// Change made to settings database, as it is recorded into history.
+pub struct NewChange<'a> {
+    pub db_path: &'a Path,
+    /// What made the change, "cli" or "watch".
+    pub source: &'a str,
+    /// Command line of logi-man invocation which made the change,
+    /// or description of change noticed by `watch`.
+    pub command: String,
+    pub before: &'a [u8],
+    pub after: &'a [u8],
+    /// Source-specific JSON details, e.g. changed paths.
+    pub details: Option<String>,
+}
+
+/// Recorded change, without settings snapshot itself.
+pub struct Change {
+    pub id: i64,
+    pub timestamp: String,
+    pub db_path: String,
+    pub source: String,
+    pub command: String,
+    pub version: String,
+    pub checksum_before: String,
+    pub checksum_after: String,
+    pub details: Option<String>,
+}
+
+/// Storage for history of settings changes made by logi-man.
+pub trait HistoryStore {
+    fn record(&self, change: &NewChange) -> Result<i64>;
+    /// Returns latest changes of given database, newest first, optionally
+    /// made by given source only.
+    fn log(&self, db_path: &Path, source: Option<&str>, limit: usize) -> Result<Vec<Change>>;
+    fn get(&self, id: i64) -> Result<Option<Change>>;
+    /// Returns settings as they were after given change.
+    fn settings(&self, id: i64) -> Result<Option<Vec<u8>>>;
+}
+
+/// History stored in logi-man's own SQLite database.
+///
+/// It is intentionally separate from Logi Options+ database, we must not
+/// change schema we don't own.
+pub struct SqliteHistory {
+    db: rusqlite::Connection,
+}
+
+const SCHEMA_VERSION: u32 = 2;
+
+impl SqliteHistory {
+    pub fn default_path() -> Result<PathBuf> {
+        let dirs = BaseDirs::new().ok_or_eyre("can't get user directory path")?;
+        Ok(dirs.data_local_dir().join("logi-man/history.db"))
+    }
+
+    pub fn open_default() -> Result<Self> {
+        Self::open(&Self::default_path()?)
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let db = rusqlite::Connection::open(path)?;
+        crate::trace_sql(&db);
+        let version: u32 = db.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version < 1 {
+            db.execute_batch("
+                CREATE TABLE changes (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    timestamp TEXT NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%S', 'now', 'localtime')),
+                    db_path TEXT NOT NULL,
+                    source TEXT NOT NULL,
+                    command TEXT NOT NULL,
+                    version TEXT NOT NULL,
+                    checksum_before TEXT NOT NULL,
+                    checksum_after TEXT NOT NULL,
+                    settings BLOB NOT NULL
+                );
+                CREATE INDEX changes_db_path ON changes (db_path);
+            ")?;
+        }
+        if version < 2 {
+            db.execute_batch("ALTER TABLE changes ADD COLUMN details TEXT")?;
+        }
+        db.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(SqliteHistory { db })
+    }
+
+    fn change_from_row(row: &rusqlite::Row) -> rusqlite::Result<Change> {
+        Ok(Change {
+            id: row.get("id")?,
+            timestamp: row.get("timestamp")?,
+            db_path: row.get("db_path")?,
+            source: row.get("source")?,
+            command: row.get("command")?,
+            version: row.get("version")?,
+            checksum_before: row.get("checksum_before")?,
+            checksum_after: row.get("checksum_after")?,
+            details: row.get("details")?,
+        })
+    }
+}
+
+const CHANGE_COLUMNS: &str = "id, timestamp, db_path, source, command, version, checksum_before, checksum_after, details";
+
+impl HistoryStore for SqliteHistory {
+    fn record(&self, change: &NewChange) -> Result<i64> {
+        self.db.execute(
+            "INSERT INTO changes (db_path, source, command, version, checksum_before, checksum_after, settings, details)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            rusqlite::params![
+                db_key(change.db_path),
+                change.source,
+                change.command,
+                env!("CARGO_PKG_VERSION"),
+                checksum(change.before),
+                checksum(change.after),
+                change.after,
+                change.details,
+            ],
+        )?;
+        Ok(self.db.last_insert_rowid())
+    }
+
+    fn log(&self, db_path: &Path, source: Option<&str>, limit: usize) -> Result<Vec<Change>> {
+        let mut stmt = self.db.prepare(&format!(
+            "SELECT {CHANGE_COLUMNS} FROM changes WHERE db_path = ?1 AND (?2 IS NULL OR source = ?2)
+             ORDER BY id DESC LIMIT ?3"))?;
+        let changes = stmt.query_map(rusqlite::params![db_key(db_path), source, limit], Self::change_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(changes)
+    }
+
+    fn get(&self, id: i64) -> Result<Option<Change>> {
+        let mut stmt = self.db.prepare(&format!("SELECT {CHANGE_COLUMNS} FROM changes WHERE id = ?1"))?;
+        let mut changes = stmt.query_map([id], Self::change_from_row)?;
+        Ok(changes.next().transpose()?)
+    }
+
+    fn settings(&self, id: i64) -> Result<Option<Vec<u8>>> {
+        let mut stmt = self.db.prepare("SELECT settings FROM changes WHERE id = ?1")?;
+        let mut settings = stmt.query_map([id], |row| row.get(0))?;
+        Ok(settings.next().transpose()?)
+    }
+}
+
+/// Same database may be referred by different paths, use canonical one.
+fn db_key(db_path: &Path) -> String {
+    std::fs::canonicalize(db_path).as_deref().unwrap_or(db_path).to_string_lossy().into_owned()
+}
+
+pub fn checksum(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Records changes made by current invocation.
+///
+/// History is nice to have, but it is not a reason to fail the whole
+/// operation after settings are already saved, so just warn.
+pub struct JournalHook;
+
+impl Hook for JournalHook {
+    fn after_save(&self, db_path: &Path, before: &[u8], after: &[u8]) {
+        let command = std::env::args().collect::<Vec<_>>().join(" ");
+        let change = NewChange { db_path, source: "cli", command, before, after, details: None };
+        match SqliteHistory::open_default().and_then(|history| history.record(&change)) {
+            Ok(id) => tracing::info!(id, "change recorded into history"),
+            Err(err) => tracing::warn!("failed to record change into history: {err:#}"),
+        }
+    }
+}
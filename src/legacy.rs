@@ -0,0 +1,212 @@
+//! Import from classic Logitech Options, predecessor of Options+.
+//!
+//! Classic Options keeps settings in plist files on macOS and in registry
+//! and XML files on Windows. Reading them directly isn't supported, settings
+//! must be converted to JSON like this:
+//!
+//! ```json
+//! {"applications": [
+//!   {"bundleId": "com.apple.Safari",
+//!    "buttons": [{"cid": "0x53", "action": {"type": "Keystroke", "keyCode": 123, "modifiers": ["cmd"]}}]}
+//! ]}
+//! ```
+//!
+//! Application without `bundleId` holds global assignments. Button control
+//! ids are the same ones Options+ uses in slot ids, `0x53` is `c83`.
+//!
+//! Key codes are macOS virtual key codes (`kVK_*` constants of Carbon
+//! `Events.h`), classic Options stores them as they are. Options+ uses USB HID
+//! usage ids of Keyboard/Keypad page instead, for both keys and modifiers, so
+//! they are translated. Keys and modifiers which can't be translated are
+//! reported and their buttons are skipped.
+
+use std::path::Path;
+
+use eyre::{bail, eyre, Result};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::{merge, models::{Assignment, Profile, Settings}, options::ImportLegacy, resolve, write_settings};
+
+#[derive(Deserialize)]
+struct LegacySettings {
+    applications: Vec<LegacyApplication>,
+}
+
+#[derive(Deserialize)]
+struct LegacyApplication {
+    #[serde(rename="bundleId", default)]
+    bundle_id: Option<String>,
+    #[serde(default)]
+    buttons: Vec<LegacyButton>,
+}
+
+#[derive(Deserialize)]
+struct LegacyButton {
+    /// Control id, either number or hex string.
+    cid: Value,
+    action: Value,
+}
+
+pub fn import_legacy(db_path: &Path, opts: ImportLegacy, db: rusqlite::Connection, old_settings: Vec<u8>) -> Result<()> {
+    let mut settings: Settings = serde_json::from_slice(&old_settings)?;
+    resolve::device(&settings, &opts.device)?;
+
+    let path = opts.path;
+    if path.extension().is_some_and(|ext| ext != "json") {
+        bail!("classic Logitech Options settings must be converted to JSON first, see `logi-man import-legacy --help`; got {}", path.display());
+    }
+    let text = std::fs::read_to_string(&path).map_err(|err| eyre!("can't read {}: {err}", path.display()))?;
+    let legacy: LegacySettings = serde_json::from_str(&text)?;
+
+    let mut report = merge::MergeReport::default();
+    for application in legacy.applications {
+        let mut assignments = Vec::new();
+        for button in application.buttons {
+            let Some(cid) = control_id(&button.cid) else {
+                tracing::warn!("skipping button with invalid control id {}", button.cid);
+                continue;
+            };
+            let action = match convert_action(&button.action) {
+                Ok(action) => action,
+                Err(reason) => {
+                    println!("unsupported: {} c{cid}: {} ({reason})", application.bundle_id.as_deref().unwrap_or("default"), button.action);
+                    continue;
+                }
+            };
+            assignments.push(Assignment::new(format!("{}_c{cid}", opts.device), &action.into()));
+        }
+        let profile = Profile { application_id: application.bundle_id, name: None, assignments, rest: Map::new() };
+        merge::import_profile(&mut settings, profile, opts.on_conflict.for_dry_run(opts.dry_run), &mut report)?;
+    }
+    report.print();
+
+    if opts.dry_run || !report.has_changes() {
+        return Ok(());
+    }
+    write_settings(db_path, &db, &old_settings, &settings, false)
+}
+
+fn control_id(cid: &Value) -> Option<u64> {
+    match cid {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => s.parse().ok(),
+        },
+        _ => None,
+    }
+}
+
+/// Converts classic action into Options+ one, for kinds of actions
+/// which exist in both. Returns reason when action can't be converted.
+fn convert_action(action: &Value) -> Result<Value, String> {
+    let kind = action.get("type").and_then(Value::as_str).ok_or("action without type")?;
+    match kind {
+        "Keystroke" => {
+            let code = action.get("keyCode").and_then(Value::as_u64).ok_or("keystroke without key code")?;
+            let code = hid_key(code).ok_or_else(|| format!("unknown key code {code}"))?;
+            let modifiers = match action.get("modifiers") {
+                None => Vec::new(),
+                Some(Value::Array(modifiers)) => modifiers.iter()
+                    .map(|modifier| modifier.as_str().and_then(hid_modifier).ok_or_else(|| format!("unknown modifier {modifier}")))
+                    .collect::<Result<_, _>>()?,
+                Some(modifiers) => return Err(format!("invalid modifiers {modifiers}")),
+            };
+            Ok(json!({"type": "KEYSTROKE", "keystroke": {"code": code, "modifiers": modifiers}}))
+        }
+        // Classic OS actions are named like "MissionControl", Options+ ones like "MISSION_CONTROL".
+        "OsAction" => {
+            let name = action.get("name").and_then(Value::as_str).ok_or("OS action without name")?;
+            let mut shortcut = String::new();
+            for (i, c) in name.char_indices() {
+                if c.is_uppercase() && i > 0 {
+                    shortcut.push('_');
+                }
+                shortcut.push(c.to_ascii_uppercase());
+            }
+            Ok(json!({"type": "OS_SHORTCUT", "shortcut": shortcut}))
+        }
+        _ => Err(format!("{kind} actions aren't supported by Options+")),
+    }
+}
+
+/// Returns HID usage id of modifier given by name.
+fn hid_modifier(name: &str) -> Option<u64> {
+    match name.to_ascii_lowercase().as_str() {
+        "ctrl" | "control" => Some(224),
+        "shift" => Some(225),
+        "alt" | "opt" | "option" => Some(226),
+        "cmd" | "command" | "meta" | "win" => Some(227),
+        _ => None,
+    }
+}
+
+/// Translates macOS virtual key code into HID usage id of the same key on
+/// ANSI layout.
+fn hid_key(code: u64) -> Option<u64> {
+    KEYS.iter().find(|(virtual_code, _)| *virtual_code == code).map(|(_, usage)| *usage)
+}
+
+/// Pairs of macOS virtual key code and HID usage id.
+const KEYS: &[(u64, u64)] = &[
+    // Letters.
+    (0x00, 0x04), (0x0b, 0x05), (0x08, 0x06), (0x02, 0x07), (0x0e, 0x08), (0x03, 0x09), (0x05, 0x0a),
+    (0x04, 0x0b), (0x22, 0x0c), (0x26, 0x0d), (0x28, 0x0e), (0x25, 0x0f), (0x2e, 0x10), (0x2d, 0x11),
+    (0x1f, 0x12), (0x23, 0x13), (0x0c, 0x14), (0x0f, 0x15), (0x01, 0x16), (0x11, 0x17), (0x20, 0x18),
+    (0x09, 0x19), (0x0d, 0x1a), (0x07, 0x1b), (0x10, 0x1c), (0x06, 0x1d),
+    // Digits 1-9 and 0.
+    (0x12, 0x1e), (0x13, 0x1f), (0x14, 0x20), (0x15, 0x21), (0x17, 0x22), (0x16, 0x23), (0x1a, 0x24),
+    (0x1c, 0x25), (0x19, 0x26), (0x1d, 0x27),
+    // Return, Escape, Delete, Tab, Space.
+    (0x24, 0x28), (0x35, 0x29), (0x33, 0x2a), (0x30, 0x2b), (0x31, 0x2c),
+    // Punctuation: - = [ ] \ ; ' ` , . /
+    (0x1b, 0x2d), (0x18, 0x2e), (0x21, 0x2f), (0x1e, 0x30), (0x2a, 0x31), (0x29, 0x33), (0x27, 0x34),
+    (0x32, 0x35), (0x2b, 0x36), (0x2f, 0x37), (0x2c, 0x38),
+    // Caps Lock.
+    (0x39, 0x39),
+    // F1-F20.
+    (0x7a, 0x3a), (0x78, 0x3b), (0x63, 0x3c), (0x76, 0x3d), (0x60, 0x3e), (0x61, 0x3f), (0x62, 0x40),
+    (0x64, 0x41), (0x65, 0x42), (0x6d, 0x43), (0x67, 0x44), (0x6f, 0x45), (0x69, 0x68), (0x6b, 0x69),
+    (0x71, 0x6a), (0x6a, 0x6b), (0x40, 0x6c), (0x4f, 0x6d), (0x50, 0x6e), (0x5a, 0x6f),
+    // Help, Home, Page Up, Forward Delete, End, Page Down.
+    (0x72, 0x75), (0x73, 0x4a), (0x74, 0x4b), (0x75, 0x4c), (0x77, 0x4d), (0x79, 0x4e),
+    // Right, Left, Down, Up.
+    (0x7c, 0x4f), (0x7b, 0x50), (0x7d, 0x51), (0x7e, 0x52),
+    // Keypad: Clear, / * - + Enter, 1-9, 0, . and =.
+    (0x47, 0x53), (0x4b, 0x54), (0x43, 0x55), (0x4e, 0x56), (0x45, 0x57), (0x4c, 0x58),
+    (0x53, 0x59), (0x54, 0x5a), (0x55, 0x5b), (0x56, 0x5c), (0x57, 0x5d), (0x58, 0x5e), (0x59, 0x5f),
+    (0x5b, 0x60), (0x5c, 0x61), (0x52, 0x62), (0x41, 0x63), (0x51, 0x67),
+    // ISO section key.
+    (0x0a, 0x64),
+    // Mute, Volume Up, Volume Down.
+    (0x4a, 0x7f), (0x48, 0x80), (0x49, 0x81),
+    // Modifiers pressed as keys: Control, Shift, Option, Command, then right ones.
+    (0x3b, 0xe0), (0x38, 0xe1), (0x3a, 0xe2), (0x37, 0xe3), (0x3e, 0xe4), (0x3c, 0xe5), (0x3d, 0xe6), (0x36, 0xe7),
+];
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::convert_action;
+
+    #[test]
+    fn keystroke_is_translated_to_hid_usage_ids() {
+        let action = json!({"type": "Keystroke", "keyCode": 123, "modifiers": ["cmd", "Shift"]});
+        assert_eq!(convert_action(&action).unwrap(),
+            json!({"type": "KEYSTROKE", "keystroke": {"code": 80, "modifiers": [227, 225]}}));
+    }
+
+    #[test]
+    fn untranslatable_keystroke_is_rejected() {
+        assert!(convert_action(&json!({"type": "Keystroke", "keyCode": 200})).is_err());
+        assert!(convert_action(&json!({"type": "Keystroke", "keyCode": 0, "modifiers": ["hyper"]})).is_err());
+    }
+
+    #[test]
+    fn os_action_name_is_converted() {
+        assert_eq!(convert_action(&json!({"type": "OsAction", "name": "MissionControl"})).unwrap(),
+            json!({"type": "OS_SHORTCUT", "shortcut": "MISSION_CONTROL"}));
+    }
+}
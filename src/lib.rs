@@ -0,0 +1,485 @@
+//! Logi Options+ settings manager.
+//!
+//! Besides command line interface, which is run with [`run`], it allows
+//! embedders to observe and validate settings changes with [`hooks`]
+//! and to read or record history of changes with [`journal`].
+
+use std::{io::{IsTerminal, Write}, path::{Path, PathBuf}, sync::atomic::{AtomicBool, Ordering}};
+
+use directories_next::BaseDirs;
+use eyre::{ensure, OptionExt, Result};
+use tracing::{debug, info};
+
+mod options;
+pub mod models;
+pub mod hooks;
+pub mod journal;
+mod doctor;
+mod completion;
+mod profiles;
+mod merge;
+mod smart_actions;
+mod flow;
+mod blob;
+mod devices;
+mod gestures;
+mod templates;
+mod tune;
+mod sanitize;
+mod agent;
+mod validate;
+mod backup;
+mod config;
+mod query;
+mod schema;
+mod actions;
+mod legacy;
+mod sync;
+mod presets;
+mod buttons;
+mod resolve;
+mod watch;
+mod onboard;
+mod plan;
+mod timeline;
+mod capture;
+
+use options::{Command, EditSettings, Export, History, HistoryCommand, Import, Options, ShowSettings, TransferAssignments};
+use models::{Assignment, Settings};
+use journal::{HistoryStore, SqliteHistory};
+
+/// Runs command line interface with arguments of current process.
+pub fn run() -> Result<()> {
+    // Handle completion requests from shell, it exits if one is detected.
+    completion::complete();
+
+    let options = Options::parse();
+    init_logging(options.common.verbose);
+    config::init()?;
+    ALLOW_PROTECTED.store(options.common.i_know_what_im_doing, Ordering::Relaxed);
+    NO_RESTART.store(options.common.no_restart, Ordering::Relaxed);
+    let backups = &config::get().backups;
+    backup::set_policy(backup::Policy {
+        keep: options.common.keep_backups.or(backups.keep),
+        max_age_days: options.common.max_backup_age.or(backups.max_age_days),
+        compress: options.common.compress_backups || backups.compress,
+    });
+    // Embedders may run command line interface several times.
+    static JOURNAL: std::sync::Once = std::sync::Once::new();
+    JOURNAL.call_once(|| hooks::register(journal::JournalHook));
+
+    let db_path = db_path(options.common.db)?;
+    let read_only = options.command.is_read_only();
+    ensure!(read_only || !options.common.read_only, "command changes settings database, but --read-only is given");
+
+    // Settings are loaded only for commands needing them. Doctor must work
+    // even when database can't be opened at all.
+    let open = || -> Result<(rusqlite::Connection, Vec<u8>)> {
+        let db = open_database(&db_path, read_only)?;
+        let settings = load_settings(&db)?;
+        hooks::on_load(&db_path, &settings)?;
+        Ok((db, settings))
+    };
+
+    match options.command {
+        Command::Doctor => doctor::doctor(&db_path),
+        Command::History(opts) => history(&db_path, &opts),
+        Command::Backup(opts) => backup::backup(&db_path, &opts),
+        Command::Completions(opts) => completion::completions(&opts.shell),
+        Command::Agent(opts) => agent::agent(&opts),
+        Command::RestartAgent => agent::restart(),
+        Command::Schema => write_output(None, &serde_json::to_string_pretty(&schema::schema())?),
+        Command::Validate => validate::validate(&open_database(&db_path, true)?),
+        Command::Status => open().and_then(|(db, settings)| status(&db_path, &db, settings)),
+        Command::ShowSettings(opts) => open().and_then(|(_, settings)| show_settings(opts, settings)),
+        Command::ListDevices => open().and_then(|(_, settings)| list_devices(settings)),
+        Command::EditSettings(opts) => open().and_then(|(db, settings)| edit_settings(&db_path, opts, db, settings)),
+        Command::TransferAssignments(opts) => open().and_then(|(db, settings)| transfer_assignments(&db_path, opts, db, settings)),
+        Command::Buttons(opts) => open().and_then(|(_, settings)| buttons::list(opts, settings)),
+        Command::Gestures(opts) => open().and_then(|(db, settings)| gestures::gestures(&db_path, opts, db, settings)),
+        Command::Tune(opts) => open().and_then(|(db, settings)| tune::tune(&db_path, opts, db, settings)),
+        Command::Query(opts) => open().and_then(|(db, settings)| query::query(opts, &db, settings)),
+        Command::Watch(opts) => open().and_then(|(db, settings)| watch::watch(&db_path, opts, &db, settings)),
+        Command::ForgetDevice(opts) => open().and_then(|(db, settings)| devices::forget_device(&db_path, opts, db, settings)),
+        Command::Repair(opts) => open().and_then(|(db, settings)| devices::repair(&db_path, opts, db, settings)),
+        Command::SuggestTransfer => open().and_then(|(_, settings)| devices::suggest_transfer(settings)),
+        Command::CompareDevices(opts) => open().and_then(|(_, settings)| devices::compare(opts, settings)),
+        Command::Onboard(opts) => open().and_then(|(db, settings)| onboard::onboard(&db_path, opts, db, settings)),
+        Command::Capture(opts) => open().and_then(|(_, settings)| capture::capture(opts, settings)),
+        Command::Restore(opts) => open().and_then(|(db, settings)| capture::restore(&db_path, opts, db, settings)),
+        Command::Run(opts) => open().and_then(|(db, settings)| plan::run(&db_path, opts, db, settings)),
+        Command::Export(opts) => open().and_then(|(_, settings)| export(opts, settings)),
+        Command::Import(opts) => open().and_then(|(db, settings)| import(&db_path, opts, db, settings)),
+        Command::Preset(opts) => open().and_then(|(db, settings)| presets::preset(&db_path, opts, db, settings)),
+        Command::Sync(opts) => open().and_then(|(db, settings)| sync::sync(&db_path, opts, db, settings)),
+        Command::ImportLegacy(opts) => open().and_then(|(db, settings)| legacy::import_legacy(&db_path, opts, db, settings)),
+        Command::Profile(opts) => open().and_then(|(db, settings)| profiles::profile(&db_path, opts, db, settings)),
+        Command::SmartActions(opts) => open().and_then(|(db, settings)| smart_actions::smart_actions(&db_path, opts, db, settings)),
+        Command::Flow(opts) => open().and_then(|(db, settings)| flow::flow(&db_path, opts, db, settings)),
+    }
+}
+
+fn status(db_path: &Path, db: &rusqlite::Connection, settings: Vec<u8>) -> Result<()> {
+    println!("Database:     {}", db_path.display());
+    println!("Journal mode: {}", journal_mode(db)?);
+    println!("Settings:     {} bytes", settings.len());
+    let settings: Settings = serde_json::from_slice(&settings)?;
+    println!("Profiles:     {}", settings.profiles.len());
+    println!("Devices:      {}", settings.known_devices().len());
+    Ok(())
+}
+
+fn show_settings(opts: ShowSettings, settings: Vec<u8>) -> Result<()> {
+    let mut out = std::io::stdout().lock();
+    if opts.yaml {
+        reformat(&settings, opts.sort_keys, &mut serde_norway::Serializer::new(&mut out))?;
+    } else if opts.pretty {
+        reformat(&settings, opts.sort_keys, &mut serde_json::Serializer::pretty(&mut out))?;
+        writeln!(out)?;
+    } else if opts.sort_keys {
+        reformat(&settings, opts.sort_keys, &mut serde_json::Serializer::new(&mut out))?;
+        writeln!(out)?;
+    } else {
+        out.write_all(&settings)?;
+    }
+    Ok(())
+}
+
+/// Writes JSON using another serializer, keeping order of keys unless
+/// they are to be sorted.
+fn reformat<S>(json: &[u8], sort_keys: bool, serializer: S) -> Result<()>
+where
+    S: serde::Serializer,
+    S::Error: Send + Sync + 'static,
+{
+    if sort_keys {
+        let mut value: serde_json::Value = serde_json::from_slice(json)?;
+        value.sort_all_objects();
+        serde::Serialize::serialize(&value, serializer)?;
+    } else {
+        serde_transcode::transcode(&mut serde_json::Deserializer::from_slice(json), serializer)?;
+    }
+    Ok(())
+}
+
+fn list_devices(settings: Vec<u8>) -> Result<()> {
+    let settings: Settings = serde_json::from_slice(&settings)?;
+    for device in settings.devices() {
+        println!("{}: {}", device.slot_prefix, settings.model_name(device));
+    }
+    Ok(())
+}
+
+fn edit_settings(db_path: &Path, opts: EditSettings, db: rusqlite::Connection, settings: Vec<u8>) -> Result<()> {
+    let new_settings = if opts.stdin {
+        let new_settings = std::io::read_to_string(std::io::stdin())?;
+        // Check before backup, there is no reason to make one for invalid input.
+        serde_json::from_str::<Settings>(&new_settings)
+            .map_err(|err| eyre::eyre!("invalid settings on standard input: {err}"))?;
+        backup::backup_database(db_path, &db)?;
+        new_settings
+    } else {
+        backup::backup_database(db_path, &db)?;
+        edit::edit(&settings)?
+    };
+    if new_settings.as_bytes() == settings {
+        return Ok(());
+    }
+
+    save_settings(db_path, &db, &settings, &new_settings)?;
+    restart_agent()?;
+    Ok(())
+}
+
+fn transfer_assignments(db_path: &Path, opts: TransferAssignments, db: rusqlite::Connection, old_settings: Vec<u8>) -> Result<()> {
+    let mut settings: Settings = serde_json::from_slice(&old_settings)?;
+    resolve::device(&settings, &opts.from)?;
+    resolve::device(&settings, &opts.to)?;
+    transfer(&mut settings, &opts.from, &opts.to);
+    write_settings(db_path, &db, &old_settings, &settings, opts.dry_run)
+}
+
+/// Replaces assignments of target device in all profiles with copies of
+/// source device ones.
+fn transfer(settings: &mut Settings, from: &str, to: &str) {
+    // Smart Actions bound to source device are duplicated for target one,
+    // copied assignments must refer new actions then.
+    let smart_action_ids = smart_actions::transfer(settings, from, to);
+
+    for (key, profile) in settings.profiles.iter_mut() {
+        // Gather and clone source assignments
+        let mut new_assignments: Vec<Assignment> = profile.assignments.iter()
+            // Get only assignments for source device, leave slot suffix only
+            .filter_map(|a| {
+                let (device, button) = a.slot_id.split_once('_')?;
+                (device == from).then(|| {
+                    let mut assignment = Assignment { slot_id: format!("{to}_{button}"), ..a.clone()};
+                    smart_actions::rewrite_references(&mut assignment, &smart_action_ids);
+                    assignment
+                })
+            })
+            .collect();
+        // Remove all existing assignments for target device.
+        let count_before = profile.assignments.len();
+        profile.assignments.retain(|a| a.slot_id.split_once('_').is_some_and(|(device, _)| device != to));
+        info!(profile = %key, copied = new_assignments.len(), removed = count_before - profile.assignments.len(),
+            "transferred assignments");
+        // Append new assignemnts.
+        profile.assignments.append(&mut new_assignments);
+    }
+}
+
+fn export(opts: Export, settings: Vec<u8>) -> Result<()> {
+    let settings: Settings = serde_json::from_slice(&settings)?;
+    let mut settings = serde_json::to_value(&settings)?;
+    if opts.sanitize {
+        sanitize::sanitize(&mut settings);
+    }
+    write_output(opts.file.as_deref(), &serde_json::to_string_pretty(&settings)?)
+}
+
+fn import(db_path: &Path, opts: Import, db: rusqlite::Connection, old_settings: Vec<u8>) -> Result<()> {
+    let mut settings: Settings = serde_json::from_slice(&old_settings)?;
+    let mut imported: Settings = templates::load(&opts.file, &opts.parameters)?;
+
+    let mut report = merge::MergeReport::default();
+    for key in &imported.profile_keys {
+        if let Some(profile) = imported.profiles.remove(key) {
+            if opts.prune {
+                merge::prune(&mut settings, &profile, &mut report);
+            }
+            merge::import_profile(&mut settings, profile, opts.on_conflict.for_dry_run(opts.dry_run), &mut report)?;
+        }
+    }
+    report.print();
+
+    if opts.dry_run || !report.has_changes() {
+        return Ok(());
+    }
+    write_settings(db_path, &db, &old_settings, &settings, false)
+}
+
+/// Writes text into file or to standard output if no file is given.
+fn write_output(file: Option<&Path>, text: &str) -> Result<()> {
+    match file {
+        Some(file) => std::fs::write(file, text)?,
+        None => println!("{text}"),
+    }
+    Ok(())
+}
+
+/// Saves modified settings, or just prints them in dry-run mode.
+fn write_settings(db_path: &Path, db: &rusqlite::Connection, old_settings: &[u8], settings: &Settings, dry_run: bool) -> Result<()> {
+    let settings = serde_json::to_string_pretty(settings)?;
+    if dry_run {
+        println!("{}", settings);
+    } else {
+        backup::backup_database(db_path, db)?;
+        save_settings(db_path, db, old_settings, &settings)?;
+
+        restart_agent()?;
+    }
+
+    Ok(())
+}
+
+fn history(db_path: &Path, opts: &History) -> Result<()> {
+    match opts.command {
+        // Timeline is built from backups, history database isn't needed.
+        HistoryCommand::Timeline { ref device, ref since } => {
+            timeline::timeline(db_path, device.as_deref(), since.as_deref())?;
+        }
+        HistoryCommand::Log { limit, ref source } => {
+            let history = SqliteHistory::open_default()?;
+            for change in history.log(db_path, source.as_deref(), limit)? {
+                println!("{:>5}  {}  {:<6} {}", change.id, change.timestamp, change.source, change.command);
+            }
+        }
+        HistoryCommand::Show { id, settings } => {
+            let history = SqliteHistory::open_default()?;
+            let change = history.get(id)?.ok_or_else(|| eyre::eyre!("no change with id {id}"))?;
+            if settings {
+                let settings = history.settings(id)?.ok_or_else(|| eyre::eyre!("no change with id {id}"))?;
+                std::io::stdout().write_all(&settings)?;
+            } else {
+                println!("Change:   {}", change.id);
+                println!("Time:     {}", change.timestamp);
+                println!("Database: {}", change.db_path);
+                println!("Source:   {}", change.source);
+                println!("Command:  {}", change.command);
+                println!("Version:  {}", change.version);
+                println!("Before:   {}", change.checksum_before);
+                println!("After:    {}", change.checksum_after);
+                if let Some(details) = change.details {
+                    let details: serde_json::Value = serde_json::from_str(&details)?;
+                    println!("Details:  {}", serde_json::to_string_pretty(&details)?.replace('\n', "\n          "));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Asks user for yes/no answer, "no" is default.
+fn confirm(prompt: &str) -> Result<bool> {
+    ask_yes_no(prompt, false)
+}
+
+/// Asks user for line of text, returns it trimmed.
+fn ask(prompt: &str) -> Result<String> {
+    print!("{prompt} ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_owned())
+}
+
+/// Asks user for yes/no answer, empty answer means `default`.
+fn ask_yes_no(prompt: &str, default: bool) -> Result<bool> {
+    print!("{prompt} {} ", if default { "[Y/n]" } else { "[y/N]" });
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(match answer.trim() {
+        "" => default,
+        answer => matches!(answer, "y" | "Y" | "yes"),
+    })
+}
+
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => tracing::Level::WARN,
+        1 => tracing::Level::INFO,
+        2 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
+    };
+    // Embedder may have installed subscriber already.
+    let _ = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_target(false)
+        .without_time()
+        .try_init();
+}
+
+fn journal_mode(db: &rusqlite::Connection) -> Result<String> {
+    Ok(db.query_row("PRAGMA journal_mode", [], |row| row.get(0))?)
+}
+
+/// Returns database path given with `--db` or `LOGI_MAN_DB`, configured
+/// one or the one Options+ uses.
+fn db_path(given: Option<PathBuf>) -> Result<PathBuf> {
+    match given.or_else(config::db) {
+        Some(path) => Ok(path),
+        None => default_db_path(),
+    }
+}
+
+fn default_db_path() -> Result<PathBuf> {
+    let dirs = BaseDirs::new().ok_or_eyre("can't get user directory path")?;
+    Ok(dirs.data_local_dir().join("LogiOptionsPlus/settings.db"))
+}
+
+/// Opens settings database. Read-only connection doesn't conflict with agent
+/// and guarantees settings aren't changed accidentally.
+fn open_database(db_path: &Path, read_only: bool) -> Result<rusqlite::Connection> {
+    // Don't use default flags, they include SQLITE_OPEN_CREATE, and mistyped path
+    // results in new empty database and confusing "no such table" error.
+    let mode = if read_only { rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY } else { rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE };
+    let flags = mode | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    info!(path = %db_path.display(), read_only, "opening database");
+    let db = rusqlite::Connection::open_with_flags(db_path, flags)
+        .map_err(|err| doctor::explain_open_error(db_path, err))?;
+    trace_sql(&db);
+    Ok(db)
+}
+
+/// Logs every statement executed on connection, without parameter values:
+/// settings blob is too large to be logged.
+fn trace_sql(db: &rusqlite::Connection) {
+    if tracing::enabled!(tracing::Level::DEBUG) {
+        db.trace_v2(rusqlite::trace::TraceEventCodes::SQLITE_TRACE_STMT, Some(|event| {
+            if let rusqlite::trace::TraceEvent::Stmt(_, sql) = event {
+                debug!(sql = sql.trim());
+            }
+        }));
+    }
+}
+
+/// Loads settings JSON, stripped of known non-JSON wrappers.
+fn load_settings(db: &rusqlite::Connection) -> Result<Vec<u8>> {
+    let blob = load_blob(db)?;
+    let (wrapper, json) = blob::unwrap(&blob);
+    ensure!(wrapper.is_some() || json.first() == Some(&b'{'),
+        "settings don't look like JSON, they start with bytes {:02x?}; run `logi-man doctor` for details",
+        &json[..json.len().min(8)]);
+    Ok(json.to_vec())
+}
+
+/// Loads settings blob as it is stored in database.
+fn load_blob(db: &rusqlite::Connection) -> Result<Vec<u8>> {
+    let number_of_rows: u32 = db.query_row("SELECT COUNT(*) FROM data", [], |row| row.get(0))?;
+    ensure!(number_of_rows == 1, "database is expected to contain single row only, but it contains {} row(s)", number_of_rows);
+    let (id, settings): (u32, Vec<u8>) = db.query_row("SELECT _id, file FROM data", [], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })?;
+    ensure!(id == 1, "settings are expected to have id==1, got {}", id);
+    Ok(settings)
+}
+
+/// Whether agent restart is postponed, set from command line.
+static NO_RESTART: AtomicBool = AtomicBool::new(false);
+
+/// Restarts agent to apply saved settings, unless user postponed it.
+fn restart_agent() -> Result<()> {
+    if NO_RESTART.load(Ordering::Relaxed) {
+        println!("Settings are saved, run `logi-man restart-agent` to apply them");
+        return Ok(());
+    }
+    agent::restart()
+}
+
+/// Whether protected databases may be changed, set from command line.
+static ALLOW_PROTECTED: AtomicBool = AtomicBool::new(false);
+
+/// Fails if database is protected by configuration, it must be checked
+/// before anything is written, backups included.
+fn ensure_unprotected(db_path: &Path) -> Result<()> {
+    if ALLOW_PROTECTED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    if let Some(rule) = config::protecting_rule(db_path) {
+        eyre::bail!("{} is protected by '{rule}' rule in configuration, pass --i-know-what-im-doing to change it anyway",
+            db_path.display());
+    }
+    Ok(())
+}
+
+/// Saves settings in transaction, which is committed only if settings read
+/// back are the same as intended, so agent never sees corrupted blob.
+fn save_settings(db_path: &Path, db: &rusqlite::Connection, old_settings: &[u8], settings: &str) -> Result<()> {
+    ensure_unprotected(db_path)?;
+    let expected: serde_json::Value = serde_json::from_str(settings)
+        .map_err(|err| eyre::eyre!("refusing to save invalid settings JSON: {err}"))?;
+    hooks::before_save(db_path, old_settings, settings.as_bytes())?;
+
+    // Take write lock right away, agent may write settings concurrently.
+    let tx = rusqlite::Transaction::new_unchecked(db, rusqlite::TransactionBehavior::Immediate)?;
+    // Wrap settings back the same way they were wrapped.
+    let (wrapper, _) = blob::unwrap(&load_blob(&tx)?);
+    let blob = blob::wrap(wrapper.as_ref(), settings.as_bytes());
+    tx.execute("UPDATE data SET file=?1 WHERE _id=1", [&blob])?;
+
+    // Transaction is rolled back when dropped on error.
+    ensure!(load_blob(&tx)? == blob, "settings read back differ from written ones, changes are rolled back");
+    let saved: serde_json::Value = serde_json::from_slice(&load_settings(&tx)?)
+        .map_err(|err| eyre::eyre!("settings read back can't be parsed, changes are rolled back: {err}"))?;
+    ensure!(saved == expected, "settings read back differ from intended ones, changes are rolled back");
+    serde_json::from_value::<Settings>(saved)
+        .map_err(|err| eyre::eyre!("settings read back don't match expected structure, changes are rolled back: {err}"))?;
+    tx.commit()?;
+    info!(bytes = settings.len(), "settings saved and verified");
+    hooks::after_save(db_path, old_settings, settings.as_bytes());
+    Ok(())
+}
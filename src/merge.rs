@@ -0,0 +1,159 @@
+use eyre::Result;
+
+use crate::{models::{new_profile_key, Profile, Settings}, options::OnConflict};
+
+/// What happened to imported item.
+pub enum Outcome {
+    Added,
+    Skipped,
+    /// Conflict reported without resolving it.
+    Conflict,
+    Overwritten,
+    Renamed(String),
+    Removed,
+}
+
+/// Per-item summary of import.
+#[derive(Default)]
+pub struct MergeReport {
+    items: Vec<(String, Outcome)>,
+}
+
+impl MergeReport {
+    pub fn add(&mut self, item: String, outcome: Outcome) {
+        self.items.push((item, outcome));
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.items.iter().any(|(_, outcome)| !matches!(outcome, Outcome::Skipped | Outcome::Conflict))
+    }
+
+    pub fn print(&self) {
+        if self.items.is_empty() {
+            println!("Nothing to import, everything is up to date");
+        }
+        for (item, outcome) in &self.items {
+            match outcome {
+                Outcome::Added => println!("added:       {item}"),
+                Outcome::Skipped => println!("skipped:     {item}"),
+                Outcome::Conflict => println!("conflict:    {item}"),
+                Outcome::Overwritten => println!("overwritten: {item}"),
+                Outcome::Renamed(name) => println!("renamed:     {item} -> {name}"),
+                Outcome::Removed => println!("removed:     {item}"),
+            }
+        }
+    }
+}
+
+/// Imports profile into settings.
+///
+/// New profiles are just added. When profile for the same application exists
+/// already, imported assignments and settings are merged into it, and existing
+/// ones which differ are resolved according to `on_conflict`. `Rename` strategy
+/// works on whole profiles instead: imported profile is added for renamed
+/// application, so it can be inspected and copied later.
+pub fn import_profile(settings: &mut Settings, mut profile: Profile, on_conflict: OnConflict, report: &mut MergeReport) -> Result<()> {
+    let name = profile.display_name().to_owned();
+    let Some(key) = settings.find_profile(&name).map(|(key, _)| key.clone()) else {
+        settings.add_profile(new_profile_key(), profile);
+        report.add(name, Outcome::Added);
+        return Ok(());
+    };
+
+    if on_conflict == OnConflict::Rename {
+        if profile.is_default() {
+            // There may be only one default profile.
+            report.add(name, Outcome::Skipped);
+            return Ok(());
+        }
+        let new_name = (1..).map(|n| if n == 1 { format!("{name}.imported") } else { format!("{name}.imported{n}") })
+            .find(|candidate| settings.find_profile(candidate).is_none())
+            .unwrap();
+        profile.application_id = Some(new_name.clone());
+        settings.add_profile(new_profile_key(), profile);
+        report.add(name, Outcome::Renamed(new_name));
+        return Ok(());
+    }
+
+    let existing = settings.profiles.get_mut(&key).unwrap();
+    for assignment in profile.assignments {
+        let item = format!("{name}: {}", assignment.slot_id);
+        match existing.assignments.iter_mut().find(|a| a.slot_id == assignment.slot_id) {
+            None => {
+                existing.assignments.push(assignment);
+                report.add(item, Outcome::Added);
+            }
+            Some(current) if *current == assignment => {}
+            Some(current) => {
+                let outcome = resolve(on_conflict, &item)?;
+                if let Outcome::Overwritten = outcome {
+                    *current = assignment;
+                }
+                report.add(item, outcome);
+            }
+        }
+    }
+    for (setting, value) in profile.rest {
+        let item = format!("{name}: {setting}");
+        match existing.rest.get_mut(&setting) {
+            None => {
+                existing.rest.insert(setting, value);
+                report.add(item, Outcome::Added);
+            }
+            Some(current) if *current == value => {}
+            Some(current) => {
+                let outcome = resolve(on_conflict, &item)?;
+                if let Outcome::Overwritten = outcome {
+                    *current = value;
+                }
+                report.add(item, outcome);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes assignments and settings which aren't in imported profile, from
+/// devices imported profile has any assignments or settings for. Other
+/// devices and profiles aren't managed by imported settings and are left
+/// intact.
+pub fn prune(settings: &mut Settings, imported: &Profile, report: &mut MergeReport) {
+    let slots: Vec<String> = settings.slot_prefixes().into_iter()
+        .filter(|slot| imported.assignments.iter().any(|a| a.device() == Some(*slot))
+            || imported.device_settings(slot).next().is_some())
+        .map(str::to_owned)
+        .collect();
+    let name = imported.display_name();
+    let Some(existing) = settings.profiles.values_mut().find(|profile| profile.display_name() == name) else { return };
+
+    for slot in &slots {
+        let managed = |key: &str| key.strip_prefix(slot.as_str()).is_some_and(|rest| rest.starts_with('_'));
+        existing.assignments.retain(|assignment| {
+            let keep = !managed(&assignment.slot_id) || imported.assignments.iter().any(|a| a.slot_id == assignment.slot_id);
+            if !keep {
+                report.add(format!("{name}: {}", assignment.slot_id), Outcome::Removed);
+            }
+            keep
+        });
+        existing.rest.retain(|key, _| {
+            let keep = !managed(key) || imported.rest.contains_key(key);
+            if !keep {
+                report.add(format!("{name}: {key}"), Outcome::Removed);
+            }
+            keep
+        });
+    }
+}
+
+/// Decides whether to overwrite existing item, for all strategies except `Rename`.
+pub fn resolve(on_conflict: OnConflict, item: &str) -> Result<Outcome> {
+    let overwrite = match on_conflict {
+        OnConflict::Report => return Ok(Outcome::Conflict),
+        OnConflict::Skip => false,
+        OnConflict::Overwrite => true,
+        OnConflict::Ask => crate::confirm(&format!("{item} already exists, overwrite?"))?,
+        OnConflict::Rename => unreachable!("renaming is handled by caller"),
+    };
+    Ok(if overwrite { Outcome::Overwritten } else { Outcome::Skipped })
+}
+
@@ -1,19 +1,190 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use serde::{de::Error as _, Deserialize, Serialize};
 use serde_json::{Map, Value};
 
 #[derive(Clone, Deserialize, Serialize)]
-#[serde(try_from="RawSettings")]
-#[serde(into="RawSettings")]
+#[serde(try_from="Value")]
+#[serde(into="Value")]
 pub struct Settings {
     pub profile_keys: Vec<String>,
     pub profiles: BTreeMap<String, Profile>,
     pub ever_connected_devices: EverConnectedDevices,
     pub migration_report: MigrationReport,
+    pub smart_actions: Option<Vec<SmartAction>>,
+    pub flow: Option<Flow>,
 
-    #[serde(flatten)]
     rest: Map<String, Value>,
+    /// Layout of settings as they were loaded, keys are written in the same
+    /// order, so that settings which aren't changed are written byte for byte.
+    layout: Value,
+}
+
+impl Settings {
+    /// Returns real devices, deduplicated and ordered by slot prefix.
+    pub fn devices(&self) -> Vec<&ConnectedDevice> {
+        self.known_devices().into_iter()
+            // There are some virtual devices in list, skip them.
+            .filter(|device| device.device_type == "MOUSE")
+            .collect()
+    }
+
+    /// Returns all devices ever connected, keyboards and virtual ones
+    /// included, deduplicated and ordered by slot prefix.
+    pub fn known_devices(&self) -> Vec<&ConnectedDevice> {
+        let devices: BTreeMap<&str, &ConnectedDevice> = self.ever_connected_devices.devices.iter()
+            // Sometimes same device is listed several times. Deduplicate records.
+            .map(|device| (device.slot_prefix.as_str(), device))
+            .collect();
+        devices.into_values().collect()
+    }
+
+    /// Finds record of device ever connected by its slot prefix.
+    pub fn device(&self, slot_prefix: &str) -> Option<&ConnectedDevice> {
+        self.ever_connected_devices.devices.iter().find(|device| device.slot_prefix == slot_prefix)
+    }
+
+    /// Returns slot prefixes of all known devices and of devices assignments
+    /// are left for after their records were removed.
+    pub fn slot_prefixes(&self) -> BTreeSet<&str> {
+        let assigned = self.profiles.values()
+            .flat_map(|profile| &profile.assignments)
+            .filter_map(|assignment| assignment.device());
+        self.ever_connected_devices.devices.iter()
+            .map(|device| device.slot_prefix.as_str())
+            .chain(assigned)
+            .collect()
+    }
+
+    /// Returns human-readable model name of device.
+    pub fn model_name<'a>(&'a self, device: &'a ConnectedDevice) -> &'a str {
+        // I have no idea where LogiOptions application gets model names,
+        // I suppose they are hardcoded into binary. But some model names
+        // are in migration settings. Use them.
+        let model_name = |model_id: &str| self.migration_report.devices.iter()
+            .find(|d| d.model_id == model_id)
+            .map(|d| d.device_name.as_str());
+        model_name(&device.device_model)
+            // Sometimes model ID in migration settings looks like '6b023',
+            // but device model in device list is '6b023_ext2'.
+            // So try to use first part before '_' to find model name.
+            .or_else(|| {
+                device.device_model.split_once('_')
+                    .and_then(|(prefix, _)| model_name(prefix))
+            })
+            // No model name found, use model id.
+            .unwrap_or(device.device_model.as_str())
+    }
+
+    /// Finds profile by its key, application id or "default" name.
+    pub fn find_profile(&self, name: &str) -> Option<(&String, &Profile)> {
+        self.profiles.iter().find(|(key, profile)| *key == name || profile.display_name() == name)
+    }
+
+    pub fn add_profile(&mut self, key: String, profile: Profile) {
+        self.profile_keys.push(key.clone());
+        self.profiles.insert(key, profile);
+    }
+
+    pub fn remove_profile(&mut self, key: &str) -> Option<Profile> {
+        self.profile_keys.retain(|k| k != key);
+        self.profiles.remove(key)
+    }
+
+    /// Returns number of assignments for device in all profiles.
+    pub fn assignment_count(&self, slot_prefix: &str) -> usize {
+        self.profiles.values()
+            .flat_map(|profile| &profile.assignments)
+            .filter(|assignment| assignment.device() == Some(slot_prefix))
+            .count()
+    }
+
+    pub fn default_profile(&self) -> Option<&Profile> {
+        self.profiles.values().find(|profile| profile.is_default())
+    }
+}
+
+/// Generates key for new profile.
+pub fn new_profile_key() -> String {
+    format!("profile-{}", uuid::Uuid::new_v4())
+}
+
+/// Name used to refer default profile on command line.
+pub const DEFAULT_PROFILE_NAME: &str = "default";
+
+impl Profile {
+    pub fn is_default(&self) -> bool {
+        self.application_id.as_deref().is_none_or(str::is_empty)
+    }
+
+    /// Returns per-device settings, their keys are prefixed with device slot prefix.
+    pub fn device_settings<'a>(&'a self, slot_prefix: &'a str) -> impl Iterator<Item = (&'a str, &'a Value)> + 'a {
+        self.rest.iter().filter_map(move |(key, value)| {
+            let setting = key.strip_prefix(slot_prefix)?.strip_prefix('_')?;
+            Some((setting, value))
+        })
+    }
+
+    /// Name of profile as it is accepted on command line.
+    pub fn display_name(&self) -> &str {
+        match self.application_id.as_deref() {
+            Some("") | None => DEFAULT_PROFILE_NAME,
+            Some(app) => app,
+        }
+    }
+}
+
+impl TryFrom<Value> for Settings {
+    type Error = serde_json::Error;
+
+    fn try_from(value: Value) -> std::result::Result<Self, Self::Error> {
+        let layout = layout(&value);
+        let raw: RawSettings = serde_json::from_value(value)?;
+        Ok(Settings { layout, ..Settings::try_from(raw)? })
+    }
+}
+
+impl From<Settings> for Value {
+    fn from(mut settings: Settings) -> Self {
+        let layout = std::mem::take(&mut settings.layout);
+        let mut value = serde_json::to_value(RawSettings::from(settings)).unwrap();
+        order_like(&mut value, &layout);
+        value
+    }
+}
+
+/// Returns structure of value: objects with their keys and arrays, without
+/// values themselves.
+fn layout(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(map.iter().map(|(key, value)| (key.clone(), layout(value))).collect()),
+        Value::Array(items) => Value::Array(items.iter().map(layout).collect()),
+        _ => Value::Null,
+    }
+}
+
+/// Orders keys of objects like in layout, keys missing in it go last.
+/// Array items are matched by position.
+fn order_like(value: &mut Value, layout: &Value) {
+    match (value, layout) {
+        (Value::Object(map), Value::Object(layout)) => {
+            let mut ordered = Map::new();
+            for (key, layout) in layout {
+                if let Some(mut value) = map.shift_remove(key) {
+                    order_like(&mut value, layout);
+                    ordered.insert(key.clone(), value);
+                }
+            }
+            ordered.append(map);
+            *map = ordered;
+        }
+        (Value::Array(items), Value::Array(layout)) => {
+            for (item, layout) in items.iter_mut().zip(layout) {
+                order_like(item, layout);
+            }
+        }
+        _ => {}
+    }
 }
 
 impl TryFrom<RawSettings> for Settings {
@@ -22,7 +193,7 @@ impl TryFrom<RawSettings> for Settings {
     fn try_from(mut raw: RawSettings) -> std::result::Result<Self, Self::Error> {
         let mut profiles = BTreeMap::new();
         for profile_name in &raw.profile_keys {
-            let profile = raw.rest.remove(profile_name)
+            let profile = raw.rest.shift_remove(profile_name)
                 .ok_or_else(|| serde_json::Error::custom(format!("missing profile: {profile_name}")))?;
             let profile: Profile = serde_json::from_value(profile)?;
             profiles.insert(profile_name.clone(), profile);
@@ -32,7 +203,10 @@ impl TryFrom<RawSettings> for Settings {
             profiles,
             ever_connected_devices: raw.ever_connected_devices,
             migration_report: raw.migration_report,
+            smart_actions: raw.smart_actions,
+            flow: raw.flow,
             rest: raw.rest,
+            layout: Value::Null,
         })
     }
 }
@@ -46,6 +220,8 @@ impl From<Settings> for RawSettings {
             profile_keys: settings.profile_keys,
             ever_connected_devices: settings.ever_connected_devices,
             migration_report: settings.migration_report,
+            smart_actions: settings.smart_actions,
+            flow: settings.flow,
             rest: settings.rest,
         }
     }
@@ -56,6 +232,10 @@ struct RawSettings {
     pub profile_keys: Vec<String>,
     pub ever_connected_devices: EverConnectedDevices,
     pub migration_report: MigrationReport,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub smart_actions: Option<Vec<SmartAction>>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub flow: Option<Flow>,
 
     #[serde(flatten)]
     pub rest: Map<String, Value>,
@@ -71,7 +251,7 @@ pub struct EverConnectedDevices {
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct ConnectedDevice {
-    #[serde(rename="connectionType")]
+    #[serde(rename="connectionType", default, skip_serializing_if="Option::is_none")]
     pub connection_type: Option<String>,
     #[serde(rename="deviceModel")]
     pub device_model: String,
@@ -79,6 +259,10 @@ pub struct ConnectedDevice {
     pub device_type: String,
     #[serde(rename="slotPrefix")]
     pub slot_prefix: String,
+    /// Time device was connected last time, either ISO 8601 string or
+    /// Unix time in seconds or milliseconds.
+    #[serde(rename="lastConnected", default, skip_serializing_if="Option::is_none")]
+    pub last_connected: Option<Value>,
 
     #[serde(flatten)]
     pub rest: Map<String, Value>,
@@ -105,13 +289,19 @@ pub struct MigrationDevice {
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Profile {
+    /// Bundle id of application profile applies to, missing or empty
+    /// for default profile.
+    #[serde(rename="applicationId", default, skip_serializing_if="Option::is_none")]
+    pub application_id: Option<String>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub name: Option<String>,
     pub assignments: Vec<Assignment>,
 
     #[serde(flatten)]
     pub rest: Map<String, Value>,
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
 pub struct Assignment {
     #[serde(rename="slotId")]
     pub slot_id: String,
@@ -119,3 +309,169 @@ pub struct Assignment {
     #[serde(flatten)]
     pub rest: Map<String, Value>,
 }
+
+impl Assignment {
+    /// Creates assignment of action to button.
+    pub fn new(slot_id: String, action: &AssignmentAction) -> Self {
+        let mut assignment = Assignment { slot_id, rest: Map::new() };
+        assignment.set_action(action);
+        assignment
+    }
+
+    /// Returns slot prefix of device assignment is for.
+    pub fn device(&self) -> Option<&str> {
+        self.slot_id.split_once('_').map(|(device, _)| device)
+    }
+
+    /// Returns action assigned to button, it is `card.macro` of assignment.
+    pub fn action(&self) -> AssignmentAction {
+        match self.rest.get("card").and_then(|card| card.get("macro")) {
+            Some(action) => AssignmentAction::from(action.clone()),
+            None => AssignmentAction::Default,
+        }
+    }
+
+    /// Replaces action assigned to button, other card fields are kept.
+    /// Unknown fields of action are kept too while kind of action is the same,
+    /// nested actions, like gesture ones, are merged the same way.
+    pub fn set_action(&mut self, action: &AssignmentAction) {
+        let card = self.rest.entry("card").or_insert_with(|| Value::Object(Map::new()));
+        if !card.is_object() {
+            *card = Value::Object(Map::new());
+        }
+        let card = card.as_object_mut().unwrap();
+        match (card.get_mut("macro"), Value::from(action.clone())) {
+            (_, Value::Null) => { card.shift_remove("macro"); }
+            (Some(Value::Object(old)), Value::Object(new)) if old.get("type") == new.get("type") => merge_action(old, new),
+            (_, new) => { card.insert("macro".to_owned(), new); }
+        }
+    }
+}
+
+/// Merges new action into old one of the same kind, nested objects of the
+/// same kind are merged recursively. Nested default actions, which are
+/// nulls, remove old ones.
+fn merge_action(old: &mut Map<String, Value>, new: Map<String, Value>) {
+    for (key, new) in new {
+        match (old.get_mut(&key), new) {
+            (_, Value::Null) => { old.shift_remove(&key); }
+            (Some(Value::Object(old)), Value::Object(new)) if old.get("type") == new.get("type") => merge_action(old, new),
+            (_, new) => { old.insert(key, new); }
+        }
+    }
+}
+
+/// Action assigned to button. Known kinds of actions are typed, others are
+/// kept as JSON. Unknown fields of known actions are dropped on conversion,
+/// but [`Assignment::set_action`] keeps them in assignment.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(from = "Value", into = "Value")]
+pub enum AssignmentAction {
+    /// No action, button does what its card or device does by default.
+    Default,
+    /// Key press, codes are HID usage ids, e.g. 227 is left Command/Windows key.
+    Keystroke { code: u32, modifiers: Vec<u32> },
+    /// Action of operating system, e.g. "MISSION_CONTROL".
+    OsShortcut { shortcut: String },
+    /// Actions of gesture button by direction: "up", "down", "left", "right"
+    /// and "press".
+    Gesture { gestures: BTreeMap<String, AssignmentAction> },
+    /// Reference to Smart Action by id.
+    SmartAction { id: String },
+    /// Action of unknown kind or shape.
+    Unknown(Value),
+}
+
+impl From<Value> for AssignmentAction {
+    fn from(value: Value) -> Self {
+        let field = |name| value.get(name).and_then(Value::as_str);
+        let number = |value: &Value| value.as_u64().and_then(|n| u32::try_from(n).ok());
+        let typed = match field("type") {
+            Some("KEYSTROKE") => value.get("keystroke").and_then(|keystroke| {
+                let code = number(keystroke.get("code")?)?;
+                let modifiers = match keystroke.get("modifiers") {
+                    Some(modifiers) => modifiers.as_array()?.iter().map(number).collect::<Option<_>>()?,
+                    None => Vec::new(),
+                };
+                Some(AssignmentAction::Keystroke { code, modifiers })
+            }),
+            Some("OS_SHORTCUT") => field("shortcut").map(|shortcut| AssignmentAction::OsShortcut { shortcut: shortcut.to_owned() }),
+            Some("GESTURE") => value.get("gestures").and_then(Value::as_object).map(|gestures| AssignmentAction::Gesture {
+                gestures: gestures.iter().map(|(direction, action)| (direction.clone(), action.clone().into())).collect(),
+            }),
+            Some("SMART_ACTION") => field("smartActionId").map(|id| AssignmentAction::SmartAction { id: id.to_owned() }),
+            _ if value.is_null() => Some(AssignmentAction::Default),
+            _ => None,
+        };
+        typed.unwrap_or(AssignmentAction::Unknown(value))
+    }
+}
+
+impl From<AssignmentAction> for Value {
+    fn from(action: AssignmentAction) -> Self {
+        match action {
+            AssignmentAction::Default => Value::Null,
+            AssignmentAction::Keystroke { code, modifiers } =>
+                serde_json::json!({"type": "KEYSTROKE", "keystroke": {"code": code, "modifiers": modifiers}}),
+            AssignmentAction::OsShortcut { shortcut } => serde_json::json!({"type": "OS_SHORTCUT", "shortcut": shortcut}),
+            AssignmentAction::Gesture { gestures } => {
+                let gestures: Map<String, Value> = gestures.into_iter().map(|(direction, action)| (direction, action.into())).collect();
+                serde_json::json!({"type": "GESTURE", "gestures": gestures})
+            }
+            AssignmentAction::SmartAction { id } => serde_json::json!({"type": "SMART_ACTION", "smartActionId": id}),
+            AssignmentAction::Unknown(value) => value,
+        }
+    }
+}
+
+/// Smart Action (macro), assignments refer it by id.
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+pub struct SmartAction {
+    pub id: String,
+    pub name: String,
+
+    #[serde(flatten)]
+    pub rest: Map<String, Value>,
+}
+
+/// Logi Flow configuration: computers mouse and keyboard move between.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Flow {
+    #[serde(default)]
+    pub computers: Vec<FlowComputer>,
+
+    #[serde(flatten)]
+    pub rest: Map<String, Value>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct FlowComputer {
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub name: Option<String>,
+    /// Screen edge pointer crosses to get to this computer.
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub edge: Option<String>,
+
+    #[serde(flatten)]
+    pub rest: Map<String, Value>,
+}
+
+/// Returns all strings found in JSON tree.
+pub fn strings(value: &Value) -> Vec<&str> {
+    match value {
+        Value::String(s) => vec![s.as_str()],
+        Value::Array(items) => items.iter().flat_map(strings).collect(),
+        Value::Object(map) => map.values().flat_map(strings).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Replaces strings in JSON tree for which `f` returns new value.
+pub fn rewrite_strings(value: &mut Value, f: &mut impl FnMut(&str) -> Option<String>) {
+    match value {
+        Value::String(s) => if let Some(new) = f(s) { *s = new },
+        Value::Array(items) => items.iter_mut().for_each(|item| rewrite_strings(item, f)),
+        Value::Object(map) => map.values_mut().for_each(|item| rewrite_strings(item, f)),
+        _ => {}
+    }
+}
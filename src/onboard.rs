@@ -0,0 +1,131 @@
+//! Guided setup of newly paired device: assignments are copied from another
+//! device and reviewed button by button, buttons new device lacks are
+//! dropped.
+
+use std::path::Path;
+
+use eyre::{ensure, OptionExt, Result};
+
+use crate::{
+    actions, buttons, devices::{self, base_model},
+    models::Settings,
+    options::Onboard,
+    resolve,
+};
+
+pub fn onboard(db_path: &Path, opts: Onboard, db: rusqlite::Connection, old_settings: Vec<u8>) -> Result<()> {
+    let mut settings: Settings = serde_json::from_slice(&old_settings)?;
+    let target = resolve::device_record(&settings, &opts.device)?.clone();
+    println!("Setting up {} ({})", target.slot_prefix, settings.model_name(&target));
+    let existing = settings.assignment_count(&target.slot_prefix);
+    if existing > 0 {
+        println!("It has {existing} assignment(s) already, they will be replaced");
+    }
+
+    let source = match &opts.from {
+        Some(from) => resolve::device(&settings, from)?.to_owned(),
+        None => {
+            let (source, count) = devices::suggest_source(&settings, &target)
+                .ok_or_eyre("there are no devices with assignments to copy from")?;
+            let prompt = format!("Copy {count} assignment(s) from {} ({})?", source.slot_prefix, settings.model_name(source));
+            if !opts.yes && !crate::ask_yes_no(&prompt, true)? {
+                println!("Use --from to choose another device");
+                return Ok(());
+            }
+            source.slot_prefix.clone()
+        }
+    };
+    crate::transfer(&mut settings, &source, &target.slot_prefix);
+
+    // Review copied assignments, buttons are named using catalog. Assignment
+    // may be kept, dropped or moved to another button of new device.
+    let buttons = buttons::buttons(base_model(&target));
+    let mut decisions = Vec::new();
+    for key in &settings.profile_keys {
+        let Some(profile) = settings.profiles.get(key) else { continue };
+        for assignment in profile.assignments.iter().filter(|a| a.device() == Some(target.slot_prefix.as_str())) {
+            let suffix = &assignment.slot_id[target.slot_prefix.len() + 1..];
+            let button = buttons.iter().find(|(s, _)| s == suffix).map(|(_, name)| name.as_str());
+            let action = actions::describe_assignment(assignment, &settings);
+            let decision = match button {
+                // Device model isn't in catalog, so nothing is known about its buttons.
+                None if buttons.is_empty() => Decision::Keep,
+                None if opts.yes => {
+                    println!("{}: {suffix}: {action}, device has no such button, dropping", profile.display_name());
+                    Decision::Drop
+                }
+                None => {
+                    let prompt = format!("{}: {suffix}: {action}, device has no such button, move to button (empty to drop)?",
+                        profile.display_name());
+                    ask_decision(&settings, &target.slot_prefix, &prompt, Decision::Drop)?
+                }
+                Some(button) => {
+                    let prompt = format!("{}: {button} ({suffix}): {action}, keep, drop or move to button? [keep]",
+                        profile.display_name());
+                    if opts.yes { println!("{prompt} keep"); Decision::Keep } else {
+                        ask_decision(&settings, &target.slot_prefix, &prompt, Decision::Keep)?
+                    }
+                }
+            };
+            decisions.push((key.clone(), assignment.slot_id.clone(), decision));
+        }
+    }
+    // Moved assignments are taken out first, so buttons may be swapped.
+    let mut moved = Vec::new();
+    for (key, slot_id, decision) in decisions {
+        let assignments = &mut settings.profiles.get_mut(&key).unwrap().assignments;
+        match decision {
+            Decision::Keep => {}
+            Decision::Drop => assignments.retain(|a| a.slot_id != slot_id),
+            Decision::Move(new_slot_id) => {
+                let index = assignments.iter().position(|a| a.slot_id == slot_id).unwrap();
+                let mut assignment = assignments.remove(index);
+                assignment.slot_id = new_slot_id;
+                moved.push((key, assignment));
+            }
+        }
+    }
+    // Moved assignment replaces one kept for that button.
+    for (key, assignment) in moved {
+        let assignments = &mut settings.profiles.get_mut(&key).unwrap().assignments;
+        assignments.retain(|a| a.slot_id != assignment.slot_id);
+        assignments.push(assignment);
+    }
+
+    let expected = settings.assignment_count(&target.slot_prefix);
+    if opts.dry_run {
+        println!("{} would get {expected} assignment(s)", target.slot_prefix);
+        return Ok(());
+    }
+    crate::write_settings(db_path, &db, &old_settings, &settings, false)?;
+
+    // Make sure agent sees what we wrote.
+    let saved: Settings = serde_json::from_slice(&crate::load_settings(&db)?)?;
+    let actual = saved.assignment_count(&target.slot_prefix);
+    ensure!(actual == expected, "{} has {actual} assignment(s) after saving, expected {expected}", target.slot_prefix);
+    println!("Done, {} has {actual} assignment(s)", target.slot_prefix);
+    Ok(())
+}
+
+enum Decision {
+    Keep,
+    Drop,
+    /// Move to another button, given by slot id.
+    Move(String),
+}
+
+/// Asks what to do with assignment until answer is understood. Button is
+/// given by slot suffix or catalog name, empty answer means `default`.
+fn ask_decision(settings: &Settings, device: &str, prompt: &str, default: Decision) -> Result<Decision> {
+    loop {
+        match crate::ask(prompt)?.as_str() {
+            "" => return Ok(default),
+            "keep" | "k" => return Ok(Decision::Keep),
+            "drop" | "d" => return Ok(Decision::Drop),
+            button => match resolve::button(settings, device, button) {
+                Ok(slot_id) => return Ok(Decision::Move(slot_id)),
+                Err(err) => println!("{err}"),
+            },
+        }
+    }
+}
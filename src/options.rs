@@ -1,4 +1,9 @@
-use clap::{Parser, Subcommand};
+use std::{path::PathBuf, time::Duration};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use clap_complete::engine::ArgValueCandidates;
+
+use crate::completion::{complete_devices, complete_profiles};
 
 #[derive(Parser)]
 pub struct Options {
@@ -9,10 +14,37 @@ pub struct Options {
     pub command: Command,
 }
 
+/// Environment variable with path to settings database.
+pub const DB_ENV: &str = "LOGI_MAN_DB";
+
 #[derive(Parser)]
 pub struct CommonOptions {
-    /// Path to LogiOptions settings database
+    /// Path to LogiOptions settings database, `db` from configuration or
+    /// Options+ one is used by default
+    #[arg(long, global = true, env = DB_ENV, value_name = "PATH")]
     pub db: Option<std::path::PathBuf>,
+    /// Log steps performed, repeat (-vv) to log SQL statements too
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    pub verbose: u8,
+    /// Change database even if it matches `protected_paths` from configuration
+    #[arg(long, global = true)]
+    pub i_know_what_im_doing: bool,
+    /// Don't restart agent after changing settings, to make several changes
+    /// and restart it once with `restart-agent`
+    #[arg(long, global = true)]
+    pub no_restart: bool,
+    /// Fail if command would change settings database
+    #[arg(long, global = true)]
+    pub read_only: bool,
+    /// Keep only this many latest backups, older ones are removed after backing up
+    #[arg(long, global = true, value_name = "COUNT", value_parser = clap::value_parser!(u64).range(1..))]
+    pub keep_backups: Option<u64>,
+    /// Remove backups older than this number of days after backing up
+    #[arg(long, global = true, value_name = "DAYS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_backup_age: Option<u64>,
+    /// Compress database backups with gzip
+    #[arg(long, global = true)]
+    pub compress_backups: bool,
 }
 
 impl Options {
@@ -21,18 +53,758 @@ impl Options {
     }
 }
 
+impl Command {
+    /// Whether command never changes settings database, so it may be opened
+    /// read-only. Commands changing settings don't change them with `--dry-run`.
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            Command::Doctor | Command::Status | Command::Validate | Command::ShowSettings(_) | Command::ListDevices |
+            Command::Buttons(_) | Command::Query(_) | Command::Watch(_) | Command::SuggestTransfer |
+            Command::CompareDevices(_) | Command::Capture(_) | Command::Export(_) | Command::Schema | Command::History(_) |
+            Command::Backup(_) | Command::Completions(_) => true,
+            Command::EditSettings(_) | Command::RestartAgent => false,
+            Command::TransferAssignments(opts) => opts.dry_run,
+            Command::Tune(opts) => opts.dry_run,
+            Command::ForgetDevice(opts) => opts.dry_run,
+            Command::Repair(opts) => opts.dry_run,
+            Command::Onboard(opts) => opts.dry_run,
+            Command::Restore(opts) => opts.dry_run,
+            Command::Run(opts) => opts.dry_run,
+            Command::Import(opts) => opts.dry_run,
+            Command::Sync(opts) => opts.dry_run,
+            Command::ImportLegacy(opts) => opts.dry_run,
+            Command::Gestures(opts) => match opts.command {
+                GesturesCommand::Show { .. } => true,
+                GesturesCommand::Set { dry_run, .. } => dry_run,
+            },
+            // Presets are saved into configuration directory.
+            Command::Preset(opts) => match opts.command {
+                PresetCommand::List | PresetCommand::Save { .. } => true,
+                PresetCommand::Apply { dry_run, .. } => dry_run,
+            },
+            Command::Profile(opts) => match opts.command {
+                ProfileCommand::List | ProfileCommand::Show { .. } | ProfileCommand::DiffAgainstDefault { .. } |
+                ProfileCommand::Export { .. } => true,
+                ProfileCommand::Copy { dry_run, .. } | ProfileCommand::Import { dry_run, .. } |
+                ProfileCommand::PruneEmpty { dry_run, .. } => dry_run,
+            },
+            Command::SmartActions(opts) => match opts.command {
+                SmartActionsCommand::List | SmartActionsCommand::Export { .. } => true,
+                SmartActionsCommand::Import { dry_run, .. } => dry_run,
+            },
+            Command::Flow(opts) => match opts.command {
+                FlowCommand::Show => true,
+                FlowCommand::Reset { dry_run } => dry_run,
+            },
+            // Agent applies settings, but doesn't change them.
+            Command::Agent(opts) => matches!(opts.command, AgentCommand::Status),
+        }
+    }
+}
+
 #[derive(Clone, Parser)]
 pub struct TransferAssignments{
+    #[arg(add = ArgValueCandidates::new(complete_devices))]
     pub from: String,
+    #[arg(add = ArgValueCandidates::new(complete_devices))]
     pub to: String,
     #[arg(long)]
     pub dry_run: bool,
 }
 
+#[derive(Clone, Parser)]
+pub struct History {
+    #[command(subcommand)]
+    pub command: HistoryCommand,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum HistoryCommand {
+    /// List changes made to settings database by logi-man, newest first
+    Log {
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Show only changes made by source: "cli" for logi-man commands,
+        /// "watch" for external changes noticed by `watch`
+        #[arg(long)]
+        source: Option<String>,
+    },
+    /// Show details of single change
+    Show {
+        id: i64,
+        /// Print settings as they were after the change
+        #[arg(long)]
+        settings: bool,
+    },
+    /// Show what changed between consecutive backups of database, whoever
+    /// made changes
+    Timeline {
+        /// Show only changes of device
+        #[arg(long, add = ArgValueCandidates::new(complete_devices))]
+        device: Option<String>,
+        /// Show only changes made after given time, as YYYY-MM-DD or YYYY-MM-DD HH:MM
+        #[arg(long, value_parser = parse_time)]
+        since: Option<String>,
+    },
+}
+
+#[derive(Clone, Parser)]
+pub struct Completions {
+    /// Shell to generate completion script for: bash, elvish, fish, powershell or zsh
+    pub shell: String,
+}
+
+#[derive(Clone, Parser)]
+pub struct Profile {
+    #[command(subcommand)]
+    pub command: ProfileCommand,
+}
+
+/// Profiles are referred by application bundle id, "default" or profile key.
+#[derive(Clone, Subcommand)]
+pub enum ProfileCommand {
+    /// List default and application-specific profiles
+    List,
+    /// Show assignments and settings overridden by profile
+    Show {
+        #[arg(add = ArgValueCandidates::new(complete_profiles))]
+        profile: String,
+    },
+    /// Copy application-specific profile to another application
+    Copy {
+        #[arg(add = ArgValueCandidates::new(complete_profiles))]
+        from: String,
+        /// Bundle id of target application
+        to: String,
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show how application profile differs from default one
+    DiffAgainstDefault {
+        #[arg(add = ArgValueCandidates::new(complete_profiles))]
+        profile: String,
+    },
+    /// Save profile into file
+    Export {
+        #[arg(add = ArgValueCandidates::new(complete_profiles))]
+        profile: String,
+        /// Output file, standard output by default
+        file: Option<PathBuf>,
+    },
+    /// Import profile saved with `profile export`
+    Import {
+        file: PathBuf,
+        /// Import profile for another application
+        #[arg(long)]
+        r#as: Option<String>,
+        #[arg(long, value_enum, default_value_t = OnConflict::Skip)]
+        on_conflict: OnConflict,
+        /// Value of template parameter declared in imported file, as NAME=VALUE
+        #[arg(long = "set", value_name = "NAME=VALUE", value_parser = parse_parameter)]
+        parameters: Vec<(String, String)>,
+        /// Only report what would be imported
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Delete application profiles which don't change anything compared to default profile
+    PruneEmpty {
+        /// Only list profiles which would be deleted
+        #[arg(long)]
+        dry_run: bool,
+        /// Don't ask for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+}
+
+/// What to do when imported profile or assignment already exists.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OnConflict {
+    /// Keep existing one
+    Skip,
+    /// Replace existing one with imported
+    Overwrite,
+    /// Import profile for renamed application, keeping existing one
+    Rename,
+    /// Ask for every conflict
+    Ask,
+    /// Only report conflict, keeping existing one; used for dry runs
+    #[value(skip)]
+    Report,
+}
+
+impl OnConflict {
+    /// Nothing is saved on dry run, so there is no reason to ask,
+    /// conflicts are reported instead.
+    pub fn for_dry_run(self, dry_run: bool) -> Self {
+        match self {
+            OnConflict::Ask if dry_run => OnConflict::Report,
+            on_conflict => on_conflict,
+        }
+    }
+}
+
+#[derive(Clone, Parser)]
+pub struct SmartActions {
+    #[command(subcommand)]
+    pub command: SmartActionsCommand,
+}
+
+/// Smart Actions are referred by name or id.
+#[derive(Clone, Subcommand)]
+pub enum SmartActionsCommand {
+    /// List Smart Actions
+    List,
+    /// Save Smart Action into file
+    Export {
+        name: String,
+        /// Output file, standard output by default
+        file: Option<PathBuf>,
+    },
+    /// Import Smart Action saved with `smart-actions export`
+    Import {
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t = OnConflict::Skip)]
+        on_conflict: OnConflict,
+        /// Value of template parameter declared in imported file, as NAME=VALUE
+        #[arg(long = "set", value_name = "NAME=VALUE", value_parser = parse_parameter)]
+        parameters: Vec<(String, String)>,
+        /// Only report what would be imported
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Clone, Parser)]
+pub struct Flow {
+    #[command(subcommand)]
+    pub command: FlowCommand,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum FlowCommand {
+    /// Show paired computers and Flow settings
+    Show,
+    /// Forget all paired computers
+    Reset {
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Clone, Parser)]
+pub struct Backup {
+    #[command(subcommand)]
+    pub command: BackupCommand,
+}
+
+/// Backups are made next to database before every change.
+#[derive(Clone, Subcommand)]
+pub enum BackupCommand {
+    /// List backups of database, oldest first
+    List,
+    /// Check that backups are intact and contain valid settings
+    Verify {
+        /// Backup files to verify
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        files: Vec<PathBuf>,
+        /// Verify all backups of database
+        #[arg(long)]
+        all: bool,
+        /// Number of backups verified in parallel, number of CPUs by default
+        #[arg(long)]
+        jobs: Option<usize>,
+    },
+}
+
+#[derive(Clone, Parser)]
+pub struct EditSettings {
+    /// Read new settings from standard input instead of opening editor
+    #[arg(long)]
+    pub stdin: bool,
+}
+
+/// Without options settings are printed as they are stored.
+#[derive(Clone, Parser)]
+pub struct ShowSettings {
+    /// Indent JSON
+    #[arg(long)]
+    pub pretty: bool,
+    /// Sort object keys, so same settings always give same text
+    #[arg(long)]
+    pub sort_keys: bool,
+    /// Print as YAML instead of JSON
+    #[arg(long)]
+    pub yaml: bool,
+}
+
+#[derive(Clone, Parser)]
+pub struct Buttons {
+    #[arg(long, add = ArgValueCandidates::new(complete_devices))]
+    pub device: String,
+}
+
+#[derive(Clone, Parser)]
+pub struct Run {
+    /// Plan file, JSON or YAML (`.yaml`, `.yml`)
+    pub plan: PathBuf,
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Parser)]
+pub struct CompareDevices {
+    /// Device assignments would be transferred from
+    #[arg(add = ArgValueCandidates::new(complete_devices))]
+    pub a: String,
+    /// Device assignments would be transferred to
+    #[arg(add = ArgValueCandidates::new(complete_devices))]
+    pub b: String,
+}
+
+#[derive(Clone, Parser)]
+pub struct Gestures {
+    #[command(subcommand)]
+    pub command: GesturesCommand,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum GesturesCommand {
+    /// Show actions assigned to gesture button
+    Show {
+        #[arg(long, add = ArgValueCandidates::new(complete_devices))]
+        device: String,
+        #[arg(long, default_value = "default", add = ArgValueCandidates::new(complete_profiles))]
+        profile: String,
+    },
+    /// Assign action to gesture direction
+    Set {
+        #[arg(long, add = ArgValueCandidates::new(complete_devices))]
+        device: String,
+        #[arg(long, default_value = "default", add = ArgValueCandidates::new(complete_profiles))]
+        profile: String,
+        #[arg(long, value_enum)]
+        direction: GestureDirection,
+        /// Action name, e.g. mission-control, app-expose or show-desktop;
+        /// none restores default action of direction
+        #[arg(long)]
+        action: String,
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum GestureDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Click without moving
+    Press,
+}
+
+/// Changes pointer and scroll settings of device. Without any setting
+/// given, shows current device settings. Only settings Options+ has already
+/// stored for device may be changed.
+#[derive(Clone, Parser)]
+pub struct Tune {
+    #[arg(long, add = ArgValueCandidates::new(complete_devices))]
+    pub device: String,
+    #[arg(long, default_value = "default", add = ArgValueCandidates::new(complete_profiles))]
+    pub profile: String,
+    /// Pointer speed in dots per inch
+    #[arg(long)]
+    pub dpi: Option<u32>,
+    /// Scroll speed at which wheel switches from ratchet to free-spin mode
+    #[arg(long)]
+    pub smartshift_threshold: Option<u32>,
+    #[arg(long, value_enum)]
+    pub natural_scroll: Option<Toggle>,
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Toggle {
+    On,
+    Off,
+}
+
+#[derive(Clone, Parser)]
+pub struct Agent {
+    #[command(subcommand)]
+    pub command: AgentCommand,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum AgentCommand {
+    /// Show whether agent is running
+    Status,
+    /// Restart agent, so it picks up changed settings
+    Restart,
+}
+
+/// Device may be given explicitly or selected with filters, all of given
+/// filters must match.
+#[derive(Clone, Parser)]
+#[command(group = clap::ArgGroup::new("selection").required(true).multiple(true))]
+pub struct ForgetDevice {
+    #[arg(group = "selection", add = ArgValueCandidates::new(complete_devices))]
+    pub device: Option<String>,
+    /// Select devices of given type, e.g. MOUSE or KEYBOARD
+    #[arg(long = "type", group = "selection")]
+    pub device_type: Option<String>,
+    /// Select devices without assignments and settings
+    #[arg(long, group = "selection")]
+    pub unused: bool,
+    /// Select devices last connected earlier than given time ago, e.g. 1y, 6m, 2w, 30d
+    #[arg(long, group = "selection", value_parser = parse_age)]
+    pub older_than: Option<u64>,
+    /// Don't ask for confirmation when devices are selected with filters
+    #[arg(long, short)]
+    pub yes: bool,
+    /// Remove device model from migration report too
+    #[arg(long)]
+    pub migration_report: bool,
+    /// Only report what would be removed
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Parser)]
+pub struct Query {
+    /// JSON pointer to value, e.g. /profile_keys/0
+    pub pointer: String,
+    /// Keep polling and print value every time it changes
+    #[arg(long)]
+    pub watch: bool,
+    /// Polling interval in seconds
+    #[arg(long, default_value = "1", value_parser = parse_interval, requires = "watch")]
+    pub interval: Duration,
+}
+
+#[derive(Clone, Parser)]
+pub struct Watch {
+    /// Polling interval in seconds
+    #[arg(long, default_value = "2", value_parser = parse_interval)]
+    pub interval: Duration,
+}
+
+#[derive(Clone, Parser)]
+pub struct Onboard {
+    /// New device
+    #[arg(add = ArgValueCandidates::new(complete_devices))]
+    pub device: String,
+    /// Device to copy assignments from, suggested if not given
+    #[arg(long, add = ArgValueCandidates::new(complete_devices))]
+    pub from: Option<String>,
+    /// Accept suggested device and all assignments without asking
+    #[arg(long, short)]
+    pub yes: bool,
+    /// Only show what would be done
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Parser)]
+pub struct Capture {
+    /// Device to capture, it may be already removed from list of devices
+    #[arg(long, add = ArgValueCandidates::new(complete_devices))]
+    pub device: String,
+    /// Output file, standard output by default
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Clone, Parser)]
+pub struct Restore {
+    /// File saved with `capture`
+    pub file: PathBuf,
+    /// Device to restore assignments and settings for, its ones are replaced
+    #[arg(long, add = ArgValueCandidates::new(complete_devices))]
+    pub device: String,
+    /// Only show what would be done
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Parser)]
+pub struct Repair {
+    /// Remove duplicate records from list of connected devices
+    #[arg(long)]
+    pub dedupe_devices: bool,
+    /// Remove assignments for devices which aren't in list of connected devices
+    #[arg(long)]
+    pub remove_orphaned_assignments: bool,
+    /// Only report what would be removed
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Parser)]
+pub struct Export {
+    /// Output file, standard output by default
+    pub file: Option<PathBuf>,
+    /// Hash or strip machine-specific and personal data, so settings can be shared
+    #[arg(long)]
+    pub sanitize: bool,
+}
+
+#[derive(Clone, Parser)]
+pub struct Import {
+    /// Settings file saved with `export`
+    pub file: PathBuf,
+    #[arg(long, value_enum, default_value_t = OnConflict::Skip)]
+    pub on_conflict: OnConflict,
+    /// Value of template parameter declared in imported file, as NAME=VALUE
+    #[arg(long = "set", value_name = "NAME=VALUE", value_parser = parse_parameter)]
+    pub parameters: Vec<(String, String)>,
+    /// Remove assignments and settings missing in imported file, from devices
+    /// it has any assignments or settings for, in profiles it contains
+    #[arg(long)]
+    pub prune: bool,
+    /// Only report what would be imported
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Parser)]
+pub struct Preset {
+    #[command(subcommand)]
+    pub command: PresetCommand,
+}
+
+/// Presets are device button layouts saved in logi-man configuration directory.
+#[derive(Clone, Subcommand)]
+pub enum PresetCommand {
+    /// List saved presets
+    List,
+    /// Save device assignments as preset
+    Save {
+        name: String,
+        #[arg(long, add = ArgValueCandidates::new(complete_devices))]
+        device: String,
+        #[arg(long, default_value = "default", add = ArgValueCandidates::new(complete_profiles))]
+        profile: String,
+        /// Save device settings, like DPI, too
+        #[arg(long)]
+        with_settings: bool,
+        /// Replace existing preset
+        #[arg(long)]
+        force: bool,
+    },
+    /// Replace device assignments with preset ones
+    Apply {
+        name: String,
+        #[arg(long, add = ArgValueCandidates::new(complete_devices))]
+        device: String,
+        #[arg(long, default_value = "default", add = ArgValueCandidates::new(complete_profiles))]
+        profile: String,
+        /// Value of template parameter declared in preset, as NAME=VALUE
+        #[arg(long = "set", value_name = "NAME=VALUE", value_parser = parse_parameter)]
+        parameters: Vec<(String, String)>,
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Clone, Parser)]
+pub struct Sync {
+    /// Settings database to sync with, e.g. copied from another computer
+    #[arg(long = "with")]
+    pub other: PathBuf,
+    #[arg(long, value_enum, default_value_t = SyncDirection::Merge)]
+    pub direction: SyncDirection,
+    /// Whose assignment wins on conflict; by default source wins for push
+    /// and pull, and you are asked for merge
+    #[arg(long, value_enum)]
+    pub prefer: Option<Side>,
+    /// Only report what would be changed
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SyncDirection {
+    /// Copy local settings to other database
+    Push,
+    /// Copy settings from other database to local one
+    Pull,
+    /// Copy settings both ways
+    Merge,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Side {
+    Local,
+    Other,
+}
+
+#[derive(Clone, Parser)]
+pub struct ImportLegacy {
+    /// Device to assign imported buttons to
+    #[arg(add = ArgValueCandidates::new(complete_devices))]
+    pub device: String,
+    /// Settings of classic Logitech Options converted to JSON:
+    /// `{"applications": [{"bundleId": ..., "buttons": [{"cid": "0x53", "action": {...}}]}]}`,
+    /// application without `bundleId` holds global assignments. Keystroke
+    /// (`keyCode`, `modifiers`) and OsAction (`name`) actions are supported,
+    /// key codes are macOS virtual ones, modifiers are `cmd`, `ctrl`, `alt`
+    /// and `shift`.
+    #[arg(long, verbatim_doc_comment)]
+    pub path: PathBuf,
+    #[arg(long, value_enum, default_value_t = OnConflict::Skip)]
+    pub on_conflict: OnConflict,
+    /// Only report what would be imported
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Parses age like "30d" into seconds.
+fn parse_age(s: &str) -> Result<u64, String> {
+    let unit = s.chars().last().ok_or("age is empty")?;
+    let number: u64 = s[..s.len() - unit.len_utf8()].parse().map_err(|_| format!("invalid age '{s}'"))?;
+    let unit_seconds = match unit {
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        'm' => 30 * 24 * 60 * 60,
+        'y' => 365 * 24 * 60 * 60,
+        _ => return Err(format!("unknown unit in '{s}', expected one of h, d, w, m, y")),
+    };
+    // Age is compared with signed Unix time.
+    number.checked_mul(unit_seconds).filter(|seconds| i64::try_from(*seconds).is_ok())
+        .ok_or_else(|| format!("age '{s}' is too large"))
+}
+
+/// Parses polling interval given in seconds, fractions are allowed.
+fn parse_interval(s: &str) -> Result<Duration, String> {
+    s.parse::<f64>().ok()
+        .filter(|seconds| *seconds > 0.0)
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("expected positive number of seconds, got '{s}'"))
+}
+
+/// Checks time is given as YYYY-MM-DD, optionally followed by time of day,
+/// and normalizes it to be compared with backup times as string.
+fn parse_time(s: &str) -> Result<String, String> {
+    let date = s.get(..10).filter(|date| {
+        date.char_indices().all(|(i, c)| if i == 4 || i == 7 { c == '-' } else { c.is_ascii_digit() })
+    });
+    match (date, s.get(10..)) {
+        (Some(_), Some("")) => Ok(s.to_owned()),
+        (Some(date), Some(time)) if time.starts_with([' ', 'T']) => Ok(format!("{date} {}", &time[1..])),
+        _ => Err(format!("expected YYYY-MM-DD or YYYY-MM-DD HH:MM, got '{s}'")),
+    }
+}
+
+fn parse_parameter(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s.split_once('=').ok_or_else(|| format!("expected NAME=VALUE, got '{s}'"))?;
+    Ok((name.to_owned(), value.to_owned()))
+}
+
 #[derive(Clone, Subcommand)]
 pub enum Command {
-    ShowSettings,
+    /// Diagnose problems with accessing settings database
+    Doctor,
+    /// Show summary of settings database
+    Status,
+    /// Check database and settings integrity, fails if problems are found
+    Validate,
+    ShowSettings(ShowSettings),
     ListDevices,
-    EditSettings,
+    EditSettings(EditSettings),
     TransferAssignments(TransferAssignments),
+    /// List buttons of device with slot suffixes used in assignments
+    Buttons(Buttons),
+    /// Inspect and edit gesture button actions
+    Gestures(Gestures),
+    /// Show or change DPI, SmartShift and scroll direction of device
+    Tune(Tune),
+    /// Print settings value by JSON pointer
+    Query(Query),
+    /// Keep polling settings and record changes made by others into history
+    Watch(Watch),
+    /// Remove device with all its assignments and settings
+    ForgetDevice(ForgetDevice),
+    /// Remove duplicate device records and orphaned assignments
+    Repair(Repair),
+    /// Suggest transferring assignments to devices which have none
+    SuggestTransfer,
+    /// Compare buttons, assignments and settings of two devices and show
+    /// what transferring assignments from the first one would lose
+    CompareDevices(CompareDevices),
+    /// Set up newly paired device step by step: copy assignments from
+    /// another device and review them button by button, keeping, dropping
+    /// or moving them to other buttons
+    Onboard(Onboard),
+    /// Save assignments and settings of device into file, to restore them
+    /// when device gets another slot prefix after re-pairing
+    Capture(Capture),
+    /// Restore device assignments and settings saved with `capture`
+    Restore(Restore),
+    /// Apply batch of operations from plan file as single change, with one
+    /// backup and agent restart; nothing is changed if any of them fails
+    Run(Run),
+    /// Save settings into file
+    Export(Export),
+    /// Import profiles from settings file saved with `export`
+    Import(Import),
+    /// Save and apply device button layouts
+    Preset(Preset),
+    /// Sync assignments and device settings with another settings database,
+    /// devices are matched by model
+    Sync(Sync),
+    /// Import button assignments from classic Logitech Options (not Options+)
+    ImportLegacy(ImportLegacy),
+    /// Inspect and copy application-specific profiles
+    Profile(Profile),
+    /// Inspect, export and import Smart Actions
+    SmartActions(SmartActions),
+    /// Inspect and reset Logi Flow configuration
+    Flow(Flow),
+    /// Print JSON Schema of settings, for editors to validate exported settings
+    Schema,
+    /// Control Logi Options+ agent
+    Agent(Agent),
+    /// Restart Logi Options+ agent so that it applies settings, same as `agent restart`
+    RestartAgent,
+    /// Inspect history of changes made by logi-man and timeline of backups
+    History(History),
+    /// List and verify database backups
+    Backup(Backup),
+    /// Print shell completion script
+    Completions(Completions),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_age, parse_time};
+
+    #[test]
+    fn age_is_parsed_into_seconds() {
+        assert_eq!(parse_age("12h"), Ok(12 * 60 * 60));
+        assert_eq!(parse_age("30d"), Ok(30 * 24 * 60 * 60));
+        assert_eq!(parse_age("2w"), Ok(14 * 24 * 60 * 60));
+        assert_eq!(parse_age("6m"), Ok(180 * 24 * 60 * 60));
+        assert_eq!(parse_age("1y"), Ok(365 * 24 * 60 * 60));
+    }
+
+    #[test]
+    fn invalid_age_is_rejected() {
+        for age in ["", "d", "10", "10s", "-1d", "1.5y", "99999999999999999w"] {
+            assert!(parse_age(age).is_err(), "{age}");
+        }
+    }
+
+    #[test]
+    fn time_is_normalized_for_comparison_with_backup_times() {
+        assert_eq!(parse_time("2025-04-01").as_deref(), Ok("2025-04-01"));
+        assert_eq!(parse_time("2025-04-01 10:15").as_deref(), Ok("2025-04-01 10:15"));
+        assert_eq!(parse_time("2025-04-01T10:15:30").as_deref(), Ok("2025-04-01 10:15:30"));
+        for time in ["", "2025-4-1", "2025/04/01", "April 1", "2025-04-01_10:15", "2025-04-01é"] {
+            assert!(parse_time(time).is_err(), "{time}");
+        }
+    }
 }
@@ -0,0 +1,141 @@
+//! Plans are batches of operations applied as single change: settings are
+//! backed up and agent is restarted once, and nothing is saved if any step
+//! fails.
+//!
+//! Plan is JSON or YAML file with list of steps:
+//!
+//! ```yaml
+//! steps:
+//!   - transfer: {from: mx-master-3-6b023, to: mx-master-3s-2b034}
+//!   - set-assignment: {device: mx-master-3s-2b034, button: back, action: mission-control}
+//!   - clear: {device: mx-master-3s-2b034, button: c86, profile: com.apple.Safari}
+//!   - tune: {device: mx-master-3s-2b034, dpi: 1600}
+//! ```
+//!
+//! Buttons are given by slot suffix or name from button catalog, actions by
+//! one of names `gestures set` accepts or as Options+ action JSON. Steps
+//! apply to default profile unless another one is given, `clear` without
+//! button clears all device assignments in profile.
+
+use std::path::Path;
+
+use eyre::{ensure, eyre, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    actions, gestures,
+    models::{Assignment, AssignmentAction, Settings, DEFAULT_PROFILE_NAME},
+    options::Run,
+    resolve, tune,
+};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Plan {
+    steps: Vec<Step>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+enum Step {
+    Transfer {
+        from: String,
+        to: String,
+    },
+    SetAssignment {
+        device: String,
+        button: String,
+        #[serde(default = "default_profile")]
+        profile: String,
+        action: Value,
+    },
+    Clear {
+        device: String,
+        button: Option<String>,
+        #[serde(default = "default_profile")]
+        profile: String,
+    },
+    Tune {
+        device: String,
+        #[serde(default = "default_profile")]
+        profile: String,
+        dpi: Option<u32>,
+        smartshift_threshold: Option<u32>,
+        natural_scroll: Option<bool>,
+    },
+}
+
+fn default_profile() -> String {
+    DEFAULT_PROFILE_NAME.to_owned()
+}
+
+pub fn run(db_path: &Path, opts: Run, db: rusqlite::Connection, old_settings: Vec<u8>) -> Result<()> {
+    let plan = load(&opts.plan)?;
+    let mut settings: Settings = serde_json::from_slice(&old_settings)?;
+    for (index, step) in plan.steps.into_iter().enumerate() {
+        apply(&mut settings, step).map_err(|err| err.wrap_err(format!("step {} failed, nothing is changed", index + 1)))?;
+    }
+    crate::write_settings(db_path, &db, &old_settings, &settings, opts.dry_run)
+}
+
+fn load(path: &Path) -> Result<Plan> {
+    let text = std::fs::read_to_string(path).map_err(|err| eyre!("can't read {}: {err}", path.display()))?;
+    // YAML is converted to JSON first, serde_norway expects enums as tags like
+    // `!transfer`, while steps are written as maps in both formats.
+    let plan: Value = if path.extension().is_some_and(|ext| ext == "yaml" || ext == "yml") {
+        serde_norway::from_str(&text).map_err(|err| eyre!("invalid plan {}: {err}", path.display()))?
+    } else {
+        serde_json::from_str(&text).map_err(|err| eyre!("invalid plan {}: {err}", path.display()))?
+    };
+    serde_json::from_value(plan).map_err(|err| eyre!("invalid plan {}: {err}", path.display()))
+}
+
+fn apply(settings: &mut Settings, step: Step) -> Result<()> {
+    match step {
+        Step::Transfer { from, to } => {
+            resolve::device(settings, &from)?;
+            resolve::device(settings, &to)?;
+            println!("transfer assignments from {from} to {to}");
+            crate::transfer(settings, &from, &to);
+        }
+        Step::SetAssignment { device, button, profile, action } => {
+            let slot_id = resolve::button(settings, &device, &button)?;
+            let key = resolve::profile(settings, &profile)?.0.clone();
+            let action = match action {
+                Value::String(name) => gestures::parse_action(&name)?,
+                action => AssignmentAction::from(action),
+            };
+            println!("{profile}: set {slot_id} to {}", actions::describe(&action, settings));
+
+            let profile = settings.profiles.get_mut(&key).unwrap();
+            match profile.assignments.iter_mut().find(|a| a.slot_id == slot_id) {
+                Some(assignment) => assignment.set_action(&action),
+                None => profile.assignments.push(Assignment::new(slot_id, &action)),
+            }
+        }
+        Step::Clear { device, button, profile } => {
+            let slot_id = match &button {
+                Some(button) => Some(resolve::button(settings, &device, button)?),
+                None => {
+                    resolve::device(settings, &device)?;
+                    None
+                }
+            };
+            let key = resolve::profile(settings, &profile)?.0.clone();
+            let assignments = &mut settings.profiles.get_mut(&key).unwrap().assignments;
+            let count = assignments.len();
+            assignments.retain(|a| match &slot_id {
+                Some(slot_id) => a.slot_id != *slot_id,
+                None => a.device() != Some(device.as_str()),
+            });
+            println!("{profile}: cleared {} assignment(s) of {device}", count - assignments.len());
+        }
+        Step::Tune { device, profile, dpi, smartshift_threshold, natural_scroll } => {
+            let changes = tune::changes(dpi, smartshift_threshold, natural_scroll);
+            ensure!(!changes.is_empty(), "tune step changes nothing, give dpi, smartshift_threshold or natural_scroll");
+            tune::apply(settings, &device, &profile, changes)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! Presets are named button layouts of a device, kept in logi-man
+//! configuration directory, e.g. "work" and "gaming" ones. They are not
+//! bound to device they are saved from and may be applied to any one.
+//!
+//! Preset is a template, so parameters may be declared in it, see
+//! [`crate::templates`].
+
+use std::path::{Path, PathBuf};
+
+use eyre::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    config,
+    models::{Assignment, Settings},
+    options::{self, PresetCommand},
+    resolve,
+    templates,
+};
+
+#[derive(Deserialize, Serialize)]
+struct Preset {
+    /// Assignments with slot ids stripped of device slot prefix, e.g. "c82".
+    assignments: Vec<Assignment>,
+    /// Device settings without slot prefix, e.g. "dpi".
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    settings: Map<String, Value>,
+}
+
+pub fn preset(db_path: &Path, opts: options::Preset, db: rusqlite::Connection, old_settings: Vec<u8>) -> Result<()> {
+    let mut settings: Settings = serde_json::from_slice(&old_settings)?;
+    match opts.command {
+        PresetCommand::List => list(),
+        PresetCommand::Save { name, device, profile, with_settings, force } =>
+            save(&settings, &name, &device, &profile, with_settings, force),
+        PresetCommand::Apply { name, device, profile, parameters, dry_run } => {
+            let preset: Preset = templates::load(&path(&name)?, &parameters)?;
+            apply(&mut settings, preset, &device, &profile)?;
+            crate::write_settings(db_path, &db, &old_settings, &settings, dry_run)
+        }
+    }
+}
+
+fn dir() -> Result<PathBuf> {
+    Ok(config::dir()?.join("presets"))
+}
+
+fn path(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        bail!("invalid preset name '{name}'");
+    }
+    Ok(dir()?.join(format!("{name}.json")))
+}
+
+fn list() -> Result<()> {
+    let entries = match std::fs::read_dir(dir()?) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.strip_suffix(".json").map(str::to_owned))
+        .collect();
+    names.sort();
+    for name in names {
+        println!("{name}");
+    }
+    Ok(())
+}
+
+fn save(settings: &Settings, name: &str, device: &str, profile: &str, with_settings: bool, force: bool) -> Result<()> {
+    let path = path(name)?;
+    if path.exists() && !force {
+        bail!("preset '{name}' already exists, use --force to replace it");
+    }
+    resolve::device(settings, device)?;
+    let (_, profile) = resolve::profile(settings, profile)?;
+
+    let assignments: Vec<Assignment> = profile.assignments.iter()
+        .filter(|a| a.device() == Some(device))
+        .map(|a| Assignment { slot_id: a.slot_id[device.len() + 1..].to_owned(), ..a.clone() })
+        .collect();
+    if assignments.is_empty() {
+        bail!("device '{device}' has no assignments in profile '{}'", profile.display_name());
+    }
+    let settings = if with_settings {
+        profile.device_settings(device).map(|(setting, value)| (setting.to_owned(), value.clone())).collect()
+    } else {
+        Map::new()
+    };
+
+    let count = assignments.len();
+    std::fs::create_dir_all(dir()?)?;
+    std::fs::write(&path, serde_json::to_string_pretty(&Preset { assignments, settings })?)?;
+    println!("Saved {count} assignment(s) to {}", path.display());
+    Ok(())
+}
+
+fn apply(settings: &mut Settings, preset: Preset, device: &str, profile: &str) -> Result<()> {
+    resolve::device(settings, device)?;
+    let key = resolve::profile(settings, profile)?.0.clone();
+    let profile = settings.profiles.get_mut(&key).unwrap();
+
+    profile.assignments.retain(|a| a.device() != Some(device));
+    profile.assignments.extend(preset.assignments.into_iter()
+        .map(|a| Assignment { slot_id: format!("{device}_{}", a.slot_id), ..a }));
+    for (setting, value) in preset.settings {
+        profile.rest.insert(format!("{device}_{setting}"), value);
+    }
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use std::{collections::BTreeMap, path::Path};
+
+use eyre::{bail, eyre, Result};
+
+use crate::{
+    actions,
+    merge::{self, MergeReport},
+    models::{new_profile_key, Assignment, Profile, Settings},
+    options::{self, ProfileCommand},
+};
+
+pub fn profile(db_path: &Path, opts: options::Profile, db: rusqlite::Connection, old_settings: Vec<u8>) -> Result<()> {
+    let mut settings: Settings = serde_json::from_slice(&old_settings)?;
+    match opts.command {
+        ProfileCommand::List => list_profiles(&settings),
+        ProfileCommand::Show { profile } => show_profile(&settings, &profile),
+        ProfileCommand::Copy { from, to, dry_run } => {
+            copy_profile(&mut settings, &from, &to)?;
+            crate::write_settings(db_path, &db, &old_settings, &settings, dry_run)
+        }
+        ProfileCommand::DiffAgainstDefault { profile } => diff_against_default(&settings, &profile),
+        ProfileCommand::Export { profile, file } => {
+            let (_, profile) = find_profile(&settings, &profile)?;
+            crate::write_output(file.as_deref(), &serde_json::to_string_pretty(profile)?)
+        }
+        ProfileCommand::Import { file, r#as, on_conflict, parameters, dry_run } => {
+            let mut profile: Profile = crate::templates::load(&file, &parameters)?;
+            if let Some(app) = r#as {
+                profile.application_id = Some(app);
+            }
+            let mut report = MergeReport::default();
+            merge::import_profile(&mut settings, profile, on_conflict.for_dry_run(dry_run), &mut report)?;
+            report.print();
+            if dry_run || !report.has_changes() {
+                return Ok(());
+            }
+            crate::write_settings(db_path, &db, &old_settings, &settings, false)
+        }
+        ProfileCommand::PruneEmpty { dry_run, yes } => {
+            let empty = empty_profiles(&settings);
+            if empty.is_empty() {
+                println!("There are no empty profiles");
+                return Ok(());
+            }
+            for key in &empty {
+                println!("{} [{key}]", settings.profiles[key].display_name());
+            }
+            if dry_run || !(yes || crate::confirm(&format!("Delete {} profile(s)?", empty.len()))?) {
+                return Ok(());
+            }
+            for key in &empty {
+                settings.remove_profile(key);
+            }
+            crate::write_settings(db_path, &db, &old_settings, &settings, false)
+        }
+    }
+}
+
+fn list_profiles(settings: &Settings) -> Result<()> {
+    // Default profile goes first, then in the order Options+ keeps them.
+    let mut profiles: Vec<(&String, &Profile)> = settings.profile_keys.iter()
+        .filter_map(|key| settings.profiles.get_key_value(key))
+        .collect();
+    profiles.sort_by_key(|(_, profile)| !profile.is_default());
+
+    for (key, profile) in profiles {
+        println!("{}: {} ({} assignments) [{}]",
+            profile.display_name(), profile.name.as_deref().unwrap_or("-"), profile.assignments.len(), key);
+    }
+    Ok(())
+}
+
+fn show_profile(settings: &Settings, name: &str) -> Result<()> {
+    let (key, profile) = find_profile(settings, name)?;
+    println!("Profile:     {key}");
+    println!("Application: {}", profile.display_name());
+    if let Some(name) = &profile.name {
+        println!("Name:        {name}");
+    }
+
+    // Group assignments by device, it's how they are shown in Options+.
+    let mut devices: BTreeMap<&str, Vec<(&str, String)>> = BTreeMap::new();
+    for assignment in &profile.assignments {
+        let (device, button) = assignment.slot_id.split_once('_').unwrap_or((&assignment.slot_id, ""));
+        devices.entry(device).or_default().push((button, actions::describe_assignment(assignment, settings)));
+    }
+    println!("Assignments:");
+    for (device, assignments) in devices {
+        println!("  {device}:");
+        for (button, action) in assignments {
+            println!("    {button}: {action}");
+        }
+    }
+
+    if !profile.rest.is_empty() {
+        println!("Settings:");
+        for (key, value) in &profile.rest {
+            println!("  {key}: {value}");
+        }
+    }
+    Ok(())
+}
+
+fn diff_against_default(settings: &Settings, name: &str) -> Result<()> {
+    let (_, profile) = find_profile(settings, name)?;
+    let default = settings.default_profile().ok_or_else(|| eyre!("there is no default profile"))?;
+
+    // Unassigned buttons and unset settings are inherited from default
+    // profile, so only things set in application profile may differ.
+    let mut devices: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for assignment in &profile.assignments {
+        let Some((device, button)) = assignment.slot_id.split_once('_') else { continue };
+        let base = default.assignments.iter().find(|a| a.slot_id == assignment.slot_id);
+        if base == Some(assignment) {
+            continue;
+        }
+        let describe = |assignment: Option<&Assignment>| assignment
+            .map_or("default".to_owned(), |assignment| actions::describe_assignment(assignment, settings));
+        devices.entry(device).or_default()
+            .push(format!("{button}: {} -> {}", describe(base), describe(Some(assignment))));
+    }
+    for device in settings.slot_prefixes() {
+        for (setting, value) in profile.device_settings(device) {
+            let base = default.device_settings(device).find(|(s, _)| *s == setting).map(|(_, value)| value);
+            if base != Some(value) {
+                devices.entry(device).or_default()
+                    .push(format!("{setting}: {} -> {value}", base.map_or("default".to_owned(), ToString::to_string)));
+            }
+        }
+    }
+
+    if devices.is_empty() {
+        println!("Profile '{}' doesn't differ from default one", profile.display_name());
+    }
+    for (device, differences) in devices {
+        println!("{device}:");
+        for difference in differences {
+            println!("  {difference}");
+        }
+    }
+    Ok(())
+}
+
+fn copy_profile(settings: &mut Settings, from: &str, to: &str) -> Result<()> {
+    let (_, source) = find_profile(settings, from)?;
+    if source.is_default() {
+        bail!("default profile can't be copied, it applies to all applications already");
+    }
+    if settings.find_profile(to).is_some() {
+        bail!("profile for '{to}' already exists");
+    }
+
+    let mut profile = source.clone();
+    profile.application_id = Some(to.to_owned());
+    if profile.name.is_some() {
+        profile.name = Some(to.to_owned());
+    }
+    settings.add_profile(new_profile_key(), profile);
+    Ok(())
+}
+
+/// Returns keys of application profiles without own assignments and settings.
+///
+/// Options+ creates profile as soon as application page is opened in UI,
+/// even if nothing is changed then.
+fn empty_profiles(settings: &Settings) -> Vec<String> {
+    let default = settings.default_profile();
+    settings.profiles.iter()
+        .filter(|(_, profile)| !profile.is_default() && profile.assignments.is_empty())
+        .filter(|(_, profile)| profile.rest.iter().all(|(key, value)| {
+            default.and_then(|default| default.rest.get(key)) == Some(value)
+        }))
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+pub fn find_profile<'a>(settings: &'a Settings, name: &str) -> Result<(&'a String, &'a Profile)> {
+    crate::resolve::profile(settings, name)
+}
@@ -0,0 +1,39 @@
+use eyre::{eyre, Result};
+use serde_json::Value;
+
+use crate::options::Query;
+
+pub fn query(opts: Query, db: &rusqlite::Connection, settings: Vec<u8>) -> Result<()> {
+    let value = lookup(&settings, &opts.pointer)?;
+    if !opts.watch {
+        println!("{}", serde_json::to_string_pretty(&value.ok_or_else(|| eyre!("no value at {}", opts.pointer))?)?);
+        return Ok(());
+    }
+
+    // Useful to find out which setting is changed by UI toggle, so
+    // print every change with time it's noticed.
+    let mut last = value;
+    print_change(db, &last)?;
+    loop {
+        std::thread::sleep(opts.interval);
+        let value = lookup(&crate::load_settings(db)?, &opts.pointer)?;
+        if value != last {
+            print_change(db, &value)?;
+            last = value;
+        }
+    }
+}
+
+fn lookup(settings: &[u8], pointer: &str) -> Result<Option<Value>> {
+    let mut settings: Value = serde_json::from_slice(settings)?;
+    Ok(settings.pointer_mut(pointer).map(Value::take))
+}
+
+fn print_change(db: &rusqlite::Connection, value: &Option<Value>) -> Result<()> {
+    let now: String = db.query_row("SELECT strftime('%H:%M:%S', 'now', 'localtime')", [], |row| row.get(0))?;
+    match value {
+        Some(value) => println!("[{now}] {value}"),
+        None => println!("[{now}] <missing>"),
+    }
+    Ok(())
+}
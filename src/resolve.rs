@@ -0,0 +1,97 @@
+//! Resolution of names given on command line. When nothing is found,
+//! error suggests similar names, typos in slot prefixes are easy to make.
+
+use eyre::{eyre, Report, Result};
+
+use crate::{buttons, models::{ConnectedDevice, Profile, Settings, SmartAction}};
+
+/// Returns candidates similar to name, the most similar first.
+pub fn suggestions<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    // Allow roughly one typo per four characters.
+    let max_distance = (name.chars().count() / 4).max(2);
+    let mut similar: Vec<(usize, &str)> = candidates.into_iter()
+        .map(|candidate| (strsim::levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    similar.sort();
+    similar.dedup_by_key(|(_, candidate)| *candidate);
+    similar.into_iter().take(3).map(|(_, candidate)| candidate).collect()
+}
+
+/// Error about unknown name, with suggestions of similar candidates.
+pub fn not_found<'a>(what: &str, name: &str, candidates: impl IntoIterator<Item = &'a str>, hint: &str) -> Report {
+    let similar = suggestions(name, candidates);
+    let mut message = format!("unknown {what} '{name}'");
+    if !similar.is_empty() {
+        let similar: Vec<String> = similar.iter().map(|s| format!("'{s}'")).collect();
+        message.push_str(&format!(", did you mean {}?", similar.join(" or ")));
+    }
+    if !hint.is_empty() {
+        message.push_str(&format!(" ({hint})"));
+    }
+    eyre!(message)
+}
+
+/// Finds device by slot prefix. Device record may be removed already,
+/// slot prefixes left in assignments are accepted too.
+pub fn device<'a>(settings: &'a Settings, slot_prefix: &str) -> Result<&'a str> {
+    let prefixes = settings.slot_prefixes();
+    prefixes.get(slot_prefix).copied().ok_or_else(|| {
+        not_found("device", slot_prefix, prefixes.iter().copied(), "see `list-devices`")
+    })
+}
+
+/// Finds record of device by slot prefix, it is needed to know device model.
+pub fn device_record<'a>(settings: &'a Settings, slot_prefix: &str) -> Result<&'a ConnectedDevice> {
+    let slot_prefix = device(settings, slot_prefix)?;
+    settings.device(slot_prefix).ok_or_else(|| eyre!("device {slot_prefix} has no record left, its model is unknown"))
+}
+
+/// Returns slot id of device button given by slot suffix or name from
+/// button catalog.
+pub fn button(settings: &Settings, device: &str, button: &str) -> Result<String> {
+    let buttons = buttons::device_buttons(settings, self::device(settings, device)?);
+    let suffix = buttons.iter().find(|(suffix, name)| suffix == button || name == button).map(|(suffix, _)| suffix.as_str());
+    match suffix {
+        Some(suffix) => Ok(format!("{device}_{suffix}")),
+        // Catalog may lack some buttons, accept anything looking like control id.
+        None if button.strip_prefix('c').is_some_and(|id| id.parse::<u32>().is_ok()) => Ok(format!("{device}_{button}")),
+        None => {
+            let names = buttons.iter().flat_map(|(suffix, name)| [suffix.as_str(), name.as_str()]);
+            Err(not_found("button", button, names, "see `buttons`"))
+        }
+    }
+}
+
+/// Finds profile by key, application id or "default" name, falling
+/// back to name given to profile in Options+.
+pub fn profile<'a>(settings: &'a Settings, name: &str) -> Result<(&'a String, &'a Profile)> {
+    let by_name = || settings.profiles.iter().find(|(_, profile)| profile.name.as_deref() == Some(name));
+    settings.find_profile(name).or_else(by_name).ok_or_else(|| {
+        let names = settings.profiles.iter()
+            .flat_map(|(key, profile)| [Some(key.as_str()), Some(profile.display_name()), profile.name.as_deref()])
+            .flatten();
+        not_found("profile", name, names, "see `profile list`")
+    })
+}
+
+/// Finds Smart Action by name or id.
+pub fn smart_action<'a>(settings: &'a Settings, name: &str) -> Result<&'a SmartAction> {
+    let actions = || settings.smart_actions.iter().flatten();
+    actions().find(|action| action.name == name || action.id == name).ok_or_else(|| {
+        not_found("Smart Action", name, actions().map(|action| action.name.as_str()), "see `smart-actions list`")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::suggestions;
+
+    #[test]
+    fn similar_names_are_suggested_closest_first() {
+        let candidates = ["mx-master-3s-2b034", "mx-master-3-6b023", "m337-1b016", "mx-master-3s-2b034"];
+        assert_eq!(suggestions("mx-master-3s-2b03", candidates), ["mx-master-3s-2b034", "mx-master-3-6b023"]);
+        assert_eq!(suggestions("mx-anywhere", candidates), Vec::<&str>::new());
+        assert_eq!(suggestions("ab", ["ba", "abc", "xyz"]), ["abc", "ba"]);
+    }
+}
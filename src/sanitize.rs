@@ -0,0 +1,121 @@
+//! Removes personal and machine-specific data from settings before sharing.
+//!
+//! Values of following keys, wherever they are, are replaced with hashes,
+//! so equal values are still equal after sanitizing and references between
+//! them aren't broken. Hashes are salted with random value generated for
+//! every export, so short values like serial numbers can't be found by
+//! hashing all possible ones:
+//!
+//! * serial numbers: `serialNumber`, `serial`, `unitId`;
+//! * computer names: `hostname`, `hostName`, `computerName`;
+//! * network addresses: `address`, `ipAddress`, `macAddress`, `bluetoothAddress`;
+//! * Flow peers: `name` of every computer in `flow.computers`.
+//!
+//! Besides that, user name is removed from paths in all strings:
+//! `/Users/john/...` becomes `/Users/user/...`.
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+const HASHED_KEYS: &[&str] = &[
+    "serialNumber", "serial", "unitId",
+    "hostname", "hostName", "computerName",
+    "address", "ipAddress", "macAddress", "bluetoothAddress",
+];
+
+const HOME_PREFIXES: &[&str] = &["/Users/", "/home/", "C:\\Users\\", "C:/Users/"];
+
+pub fn sanitize(settings: &mut Value) {
+    let salt = uuid::Uuid::new_v4();
+    let salt = salt.as_bytes();
+    if let Some(computers) = settings.pointer_mut("/flow/computers").and_then(Value::as_array_mut) {
+        for computer in computers {
+            if let Some(name) = computer.get_mut("name") {
+                hash(name, salt);
+            }
+        }
+    }
+    sanitize_value(settings, salt);
+}
+
+fn sanitize_value(value: &mut Value, salt: &[u8]) {
+    match value {
+        Value::String(s) => {
+            if let Some(sanitized) = strip_user_name(s) {
+                *s = sanitized;
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| sanitize_value(item, salt)),
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if HASHED_KEYS.contains(&key.as_str()) {
+                    hash(value, salt);
+                } else {
+                    sanitize_value(value, salt);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn hash(value: &mut Value, salt: &[u8]) {
+    let text = match &*value {
+        Value::String(s) => s.clone(),
+        Value::Null => return,
+        other => other.to_string(),
+    };
+    let digest = Sha256::new().chain_update(salt).chain_update(text.as_bytes()).finalize();
+    let hex: String = digest[..6].iter().map(|byte| format!("{byte:02x}")).collect();
+    *value = Value::String(format!("redacted-{hex}"));
+}
+
+fn strip_user_name(s: &str) -> Option<String> {
+    let mut result = String::new();
+    let mut rest = s;
+    let mut changed = false;
+    while let Some((index, prefix)) = HOME_PREFIXES.iter()
+        .filter_map(|prefix| rest.find(prefix).map(|index| (index, prefix)))
+        .min()
+    {
+        let after = &rest[index + prefix.len()..];
+        let name_len = after.find(['/', '\\']).unwrap_or(after.len());
+        result.push_str(&rest[..index + prefix.len()]);
+        result.push_str("user");
+        rest = &after[name_len..];
+        changed = true;
+    }
+    changed.then(|| result + rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{sanitize, strip_user_name};
+
+    #[test]
+    fn equal_values_are_hashed_equally_within_export() {
+        let mut settings = json!({"devices": [{"serialNumber": "ABC"}, {"serialNumber": "ABC"}, {"serialNumber": "DEF"}]});
+        sanitize(&mut settings);
+        let devices = &settings["devices"];
+        assert_eq!(devices[0], devices[1]);
+        assert_ne!(devices[0], devices[2]);
+        assert!(devices[0]["serialNumber"].as_str().unwrap().starts_with("redacted-"));
+    }
+
+    #[test]
+    fn hashes_differ_between_exports() {
+        let (mut a, mut b) = (json!({"serialNumber": "ABC"}), json!({"serialNumber": "ABC"}));
+        sanitize(&mut a);
+        sanitize(&mut b);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn user_name_is_stripped_from_every_path() {
+        assert_eq!(strip_user_name("/Users/alice/Applications/Foo.app").as_deref(), Some("/Users/user/Applications/Foo.app"));
+        assert_eq!(strip_user_name(r"C:\Users\bob\AppData and /home/carol").as_deref(), Some(r"C:\Users\user\AppData and /home/user"));
+        assert_eq!(strip_user_name("/Applications/Safari.app"), None);
+    }
+}
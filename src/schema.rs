@@ -0,0 +1,162 @@
+//! JSON Schema of settings, built by hand from `models` types and nested
+//! structures known so far. Unknown properties are allowed everywhere,
+//! exactly like `rest` maps of models keep them.
+
+use serde_json::{json, Value};
+
+pub fn schema() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Logi Options+ settings",
+        "type": "object",
+        "required": ["profile_keys", "ever_connected_devices", "migration_report"],
+        "properties": {
+            "$parameters": { "$ref": "#/$defs/TemplateParameters" },
+            "profile_keys": {
+                "description": "Keys of profile objects, in the order Options+ keeps them",
+                "type": "array",
+                "items": { "type": "string" },
+            },
+            "ever_connected_devices": {
+                "type": "object",
+                "required": ["devices"],
+                "properties": {
+                    "devices": { "type": "array", "items": { "$ref": "#/$defs/ConnectedDevice" } },
+                },
+            },
+            "migration_report": {
+                "type": "object",
+                "required": ["devices"],
+                "properties": {
+                    "devices": { "type": "array", "items": { "$ref": "#/$defs/MigrationDevice" } },
+                },
+            },
+            "smart_actions": { "type": "array", "items": { "$ref": "#/$defs/SmartAction" } },
+            "flow": { "$ref": "#/$defs/Flow" },
+        },
+        "patternProperties": {
+            "^profile-": { "$ref": "#/$defs/Profile" },
+        },
+        "$defs": {
+            "ConnectedDevice": {
+                "type": "object",
+                "required": ["deviceModel", "deviceType", "slotPrefix"],
+                "properties": {
+                    "connectionType": { "type": ["string", "null"] },
+                    "deviceModel": { "type": "string" },
+                    "deviceType": { "type": "string", "examples": ["MOUSE"] },
+                    "slotPrefix": {
+                        "description": "Device id, prefix of all slot ids of this device",
+                        "type": "string",
+                    },
+                },
+            },
+            "MigrationDevice": {
+                "type": "object",
+                "required": ["deviceName", "modelId"],
+                "properties": {
+                    "deviceName": { "type": "string" },
+                    "modelId": { "type": "string" },
+                },
+            },
+            "Profile": {
+                "type": "object",
+                "required": ["assignments"],
+                "properties": {
+                    "applicationId": {
+                        "description": "Bundle id of application, missing or empty for default profile",
+                        "type": "string",
+                    },
+                    "name": { "type": "string" },
+                    "assignments": { "type": "array", "items": { "$ref": "#/$defs/Assignment" } },
+                },
+                "patternProperties": {
+                    "_dpi$": { "type": "integer", "minimum": 0 },
+                    "_smartshift_threshold$": { "type": "integer", "minimum": 0 },
+                    "_natural_scroll$": { "type": "boolean" },
+                },
+            },
+            "Assignment": {
+                "type": "object",
+                "required": ["slotId"],
+                "properties": {
+                    "slotId": {
+                        "description": "Device slot prefix and button, separated by '_'",
+                        "type": "string",
+                        "pattern": "^[^_]+_.+$",
+                    },
+                    "card": {
+                        "type": "object",
+                        "properties": {
+                            "id": { "type": "string" },
+                            "macro": { "$ref": "#/$defs/Action" },
+                        },
+                    },
+                },
+            },
+            "Action": {
+                "type": "object",
+                "properties": {
+                    "type": {
+                        "type": "string",
+                        "examples": ["KEYSTROKE", "OS_SHORTCUT", "GESTURE", "SMART_ACTION"],
+                    },
+                    "keystroke": {
+                        "type": "object",
+                        "properties": {
+                            "code": { "type": "integer" },
+                            "modifiers": { "type": "array", "items": { "type": "integer" } },
+                        },
+                    },
+                    "shortcut": { "type": "string", "examples": ["MISSION_CONTROL"] },
+                    "gestures": {
+                        "type": "object",
+                        "properties": {
+                            "up": { "$ref": "#/$defs/Action" },
+                            "down": { "$ref": "#/$defs/Action" },
+                            "left": { "$ref": "#/$defs/Action" },
+                            "right": { "$ref": "#/$defs/Action" },
+                            "press": { "$ref": "#/$defs/Action" },
+                        },
+                    },
+                    "smartActionId": { "type": "string" },
+                },
+            },
+            "SmartAction": {
+                "type": "object",
+                "required": ["id", "name"],
+                "properties": {
+                    "id": { "type": "string" },
+                    "name": { "type": "string" },
+                },
+            },
+            "Flow": {
+                "type": "object",
+                "properties": {
+                    "computers": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "name": { "type": "string" },
+                                "edge": { "type": "string", "examples": ["LEFT", "RIGHT", "TOP", "BOTTOM"] },
+                            },
+                        },
+                    },
+                },
+            },
+            "TemplateParameters": {
+                "description": "Parameters of shared configuration file, substituted into strings as {name}",
+                "type": "object",
+                "additionalProperties": {
+                    "type": "object",
+                    "properties": {
+                        "description": { "type": "string" },
+                        "default": { "type": "string" },
+                    },
+                    "additionalProperties": false,
+                },
+            },
+        },
+    })
+}
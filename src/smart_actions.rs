@@ -0,0 +1,118 @@
+use std::{collections::HashMap, path::Path};
+
+use eyre::Result;
+use serde_json::Value;
+
+use crate::{
+    merge::{self, MergeReport, Outcome},
+    models::{self, Assignment, Settings, SmartAction},
+    options::{OnConflict, SmartActions, SmartActionsCommand},
+};
+
+pub fn smart_actions(db_path: &Path, opts: SmartActions, db: rusqlite::Connection, old_settings: Vec<u8>) -> Result<()> {
+    let mut settings: Settings = serde_json::from_slice(&old_settings)?;
+    match opts.command {
+        SmartActionsCommand::List => {
+            for action in settings.smart_actions.iter().flatten() {
+                println!("{} [{}]", action.name, action.id);
+            }
+            Ok(())
+        }
+        SmartActionsCommand::Export { name, file } => {
+            let action = crate::resolve::smart_action(&settings, &name)?;
+            crate::write_output(file.as_deref(), &serde_json::to_string_pretty(action)?)
+        }
+        SmartActionsCommand::Import { file, on_conflict, parameters, dry_run } => {
+            let action: SmartAction = crate::templates::load(&file, &parameters)?;
+            let mut report = MergeReport::default();
+            import(&mut settings, action, on_conflict.for_dry_run(dry_run), &mut report)?;
+            report.print();
+            if dry_run || !report.has_changes() {
+                return Ok(());
+            }
+            crate::write_settings(db_path, &db, &old_settings, &settings, false)
+        }
+    }
+}
+
+/// Imports Smart Action, conflicts are detected by name.
+fn import(settings: &mut Settings, mut action: SmartAction, on_conflict: OnConflict, report: &mut MergeReport) -> Result<()> {
+    let actions = settings.smart_actions.get_or_insert_default();
+    let name = action.name.clone();
+
+    if let Some(existing) = actions.iter_mut().find(|a| a.name == action.name) {
+        // Keep id of existing action, assignments refer it.
+        action.id = existing.id.clone();
+        if *existing == action {
+            return Ok(());
+        }
+        if on_conflict != OnConflict::Rename {
+            let outcome = merge::resolve(on_conflict, &name)?;
+            if let Outcome::Overwritten = outcome {
+                *existing = action;
+            }
+            report.add(name, outcome);
+            return Ok(());
+        }
+        action.id = uuid::Uuid::new_v4().to_string();
+        action.name = (2..).map(|n| format!("{name} ({n})"))
+            .find(|candidate| actions.iter().all(|a| a.name != *candidate))
+            .unwrap();
+        report.add(name, Outcome::Renamed(action.name.clone()));
+    } else {
+        if actions.iter().any(|a| a.id == action.id) {
+            action.id = uuid::Uuid::new_v4().to_string();
+        }
+        report.add(name, Outcome::Added);
+    }
+    actions.push(action);
+    Ok(())
+}
+
+/// Duplicates Smart Actions bound to `from` device for `to` device, replacing
+/// ones bound to `to` before. Returns mapping from ids of original actions to
+/// ids of their copies.
+pub fn transfer(settings: &mut Settings, from: &str, to: &str) -> HashMap<String, String> {
+    let mut ids = HashMap::new();
+    let Some(actions) = settings.smart_actions.as_mut() else { return ids };
+
+    actions.retain(|action| !is_bound_to(action, to));
+    let copies: Vec<SmartAction> = actions.iter()
+        .filter(|action| is_bound_to(action, from))
+        .map(|action| {
+            let mut rest = Value::Object(action.rest.clone());
+            models::rewrite_strings(&mut rest, &mut |s| rewrite_slot(s, from, to));
+            let Value::Object(rest) = rest else { unreachable!() };
+            let copy = SmartAction { id: uuid::Uuid::new_v4().to_string(), name: format!("{} ({to})", action.name), rest };
+            tracing::info!(from = action.id, to = copy.id, "copied Smart Action");
+            ids.insert(action.id.clone(), copy.id.clone());
+            copy
+        })
+        .collect();
+    actions.extend(copies);
+    ids
+}
+
+/// Makes assignment refer copies of Smart Actions instead of originals.
+pub fn rewrite_references(assignment: &mut Assignment, ids: &HashMap<String, String>) {
+    if ids.is_empty() {
+        return;
+    }
+    for value in assignment.rest.values_mut() {
+        models::rewrite_strings(value, &mut |s| ids.get(s).cloned());
+    }
+}
+
+/// Smart Action is bound to device when it refers device or its slots.
+pub fn is_bound_to(action: &SmartAction, device: &str) -> bool {
+    action.rest.values().flat_map(models::strings).any(|s| rewrite_slot(s, device, "").is_some())
+}
+
+/// Replaces device slot prefix in device or slot id.
+pub fn rewrite_slot(s: &str, from: &str, to: &str) -> Option<String> {
+    if s == from {
+        return Some(to.to_owned());
+    }
+    let button = s.strip_prefix(from)?.strip_prefix('_')?;
+    Some(format!("{to}_{button}"))
+}
@@ -0,0 +1,118 @@
+//! Synchronization of settings between databases of different computers.
+//!
+//! Only assignments and per-device settings are synced. Devices get different
+//! slot prefixes on every computer, so they are matched by model.
+//! Smart Actions aren't synced, use `smart-actions export` and `import` for them.
+
+use std::path::Path;
+
+use eyre::{bail, Result};
+use serde_json::Map;
+
+use crate::{
+    backup, devices::base_model, merge,
+    models::{Assignment, Profile, Settings},
+    options::{OnConflict, Side, Sync, SyncDirection},
+};
+
+pub fn sync(db_path: &Path, opts: Sync, db: rusqlite::Connection, old_settings: Vec<u8>) -> Result<()> {
+    let mut local: Settings = serde_json::from_slice(&old_settings)?;
+    let other_db = crate::open_database(&opts.other, opts.dry_run)?;
+    let other_old_settings = crate::load_settings(&other_db)?;
+    let mut other: Settings = serde_json::from_slice(&other_old_settings)?;
+
+    let pairs = match_devices(&local, &other);
+    if pairs.is_empty() {
+        bail!("there are no devices of the same model in both databases");
+    }
+    for (local_device, other_device) in &pairs {
+        println!("{local_device} <-> {other_device}");
+    }
+
+    let mut local_changed = false;
+    if matches!(opts.direction, SyncDirection::Pull | SyncDirection::Merge) {
+        println!("\nFrom {}:", opts.other.display());
+        let pairs: Vec<_> = pairs.iter().map(|(local, other)| (other.clone(), local.clone())).collect();
+        let on_conflict = match opts.prefer {
+            None if opts.direction == SyncDirection::Merge => OnConflict::Ask.for_dry_run(opts.dry_run),
+            None | Some(Side::Other) => OnConflict::Overwrite,
+            Some(Side::Local) => OnConflict::Skip,
+        };
+        local_changed = copy(&other, &mut local, &pairs, on_conflict)?;
+    }
+
+    let mut other_changed = false;
+    if matches!(opts.direction, SyncDirection::Push | SyncDirection::Merge) {
+        println!("\nTo {}:", opts.other.display());
+        let on_conflict = match opts.prefer {
+            // Conflicts are left unresolved while pulling on dry run, report them again.
+            None if opts.direction == SyncDirection::Merge && opts.dry_run => OnConflict::Report,
+            // Conflicts are already resolved in local settings while pulling.
+            _ if opts.direction == SyncDirection::Merge => OnConflict::Overwrite,
+            None | Some(Side::Local) => OnConflict::Overwrite,
+            Some(Side::Other) => OnConflict::Skip,
+        };
+        other_changed = copy(&local, &mut other, &pairs, on_conflict)?;
+    }
+
+    if opts.dry_run {
+        return Ok(());
+    }
+    // Check everything before writing, so that databases aren't left half-synced.
+    let other_settings = other_changed.then(|| serde_json::to_string_pretty(&other)).transpose()?;
+    let local_settings = local_changed.then(|| serde_json::to_string_pretty(&local)).transpose()?;
+    if other_settings.is_some() {
+        crate::ensure_unprotected(&opts.other)?;
+    }
+    if local_settings.is_some() {
+        crate::ensure_unprotected(db_path)?;
+    }
+    if let Some(settings) = other_settings {
+        // Other database belongs to agent of another computer, so don't restart local one.
+        backup::backup_database(&opts.other, &other_db)?;
+        crate::save_settings(&opts.other, &other_db, &other_old_settings, &settings)?;
+    }
+    if let Some(settings) = local_settings {
+        backup::backup_database(db_path, &db)?;
+        crate::save_settings(db_path, &db, &old_settings, &settings)?;
+        crate::restart_agent()?;
+    }
+    Ok(())
+}
+
+/// Returns pairs of slot prefixes of local and other devices of the same model.
+fn match_devices(local: &Settings, other: &Settings) -> Vec<(String, String)> {
+    let mut other_devices = other.known_devices();
+    let mut pairs = Vec::new();
+    for device in local.known_devices() {
+        if let Some(index) = other_devices.iter().position(|d| base_model(d) == base_model(device)) {
+            pairs.push((device.slot_prefix.clone(), other_devices.remove(index).slot_prefix.clone()));
+        }
+    }
+    pairs
+}
+
+/// Copies assignments and settings of source devices to target ones,
+/// returns whether anything changed.
+fn copy(from: &Settings, to: &mut Settings, pairs: &[(String, String)], on_conflict: OnConflict) -> Result<bool> {
+    let mut report = merge::MergeReport::default();
+    for key in &from.profile_keys {
+        let Some(profile) = from.profiles.get(key) else { continue };
+        let mut assignments = Vec::new();
+        let mut rest = Map::new();
+        for (source, target) in pairs {
+            assignments.extend(profile.assignments.iter()
+                .filter(|a| a.device() == Some(source.as_str()))
+                .map(|a| Assignment { slot_id: format!("{target}{}", &a.slot_id[source.len()..]), ..a.clone() }));
+            rest.extend(profile.device_settings(source)
+                .map(|(setting, value)| (format!("{target}_{setting}"), value.clone())));
+        }
+        if assignments.is_empty() && rest.is_empty() {
+            continue;
+        }
+        let profile = Profile { application_id: profile.application_id.clone(), name: profile.name.clone(), assignments, rest };
+        merge::import_profile(to, profile, on_conflict, &mut report)?;
+    }
+    report.print();
+    Ok(report.has_changes())
+}
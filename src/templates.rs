@@ -0,0 +1,92 @@
+//! Shared configuration files may declare parameters, which are substituted
+//! into all strings written as `{name}`. This way configuration adapts to
+//! environment of each user, e.g. path of preferred browser.
+//!
+//! Parameters are declared in top-level "$parameters" object:
+//! ```json
+//! "$parameters": {
+//!     "browser": { "description": "Browser bundle id", "default": "com.apple.Safari" }
+//! }
+//! ```
+
+use std::{io::{IsTerminal, Write}, path::Path};
+
+use eyre::{bail, eyre, Result};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{Map, Value};
+
+use crate::models;
+
+const PARAMETERS_KEY: &str = "$parameters";
+
+#[derive(Deserialize)]
+struct Parameter {
+    description: Option<String>,
+    default: Option<String>,
+}
+
+/// Loads file and substitutes parameters declared in it.
+///
+/// Values are taken from `given` ones first, then asked for interactively,
+/// then default value is used.
+pub fn load<T: DeserializeOwned>(path: &Path, given: &[(String, String)]) -> Result<T> {
+    let mut value: Value = serde_json::from_slice(&std::fs::read(path)?)?;
+    substitute(&mut value, given)?;
+    Ok(serde_json::from_value(value)?)
+}
+
+fn substitute(value: &mut Value, given: &[(String, String)]) -> Result<()> {
+    let declared = match value.as_object_mut().and_then(|object| object.shift_remove(PARAMETERS_KEY)) {
+        Some(declared) => serde_json::from_value::<Map<String, Value>>(declared)?,
+        None => Map::new(),
+    };
+    if let Some((name, _)) = given.iter().find(|(name, _)| !declared.contains_key(name)) {
+        bail!("parameter '{name}' isn't declared in file");
+    }
+
+    let mut values = Vec::new();
+    for (name, parameter) in declared {
+        let parameter: Parameter = serde_json::from_value(parameter)
+            .map_err(|err| eyre!("invalid declaration of parameter '{name}': {err}"))?;
+        let value = match given.iter().rev().find(|(n, _)| *n == name) {
+            Some((_, value)) => value.clone(),
+            None => ask(&name, &parameter)?,
+        };
+        values.push((format!("{{{name}}}"), value));
+    }
+    if values.is_empty() {
+        return Ok(());
+    }
+
+    models::rewrite_strings(value, &mut |s| {
+        values.iter().any(|(placeholder, _)| s.contains(placeholder)).then(|| {
+            values.iter().fold(s.to_owned(), |s, (placeholder, value)| s.replace(placeholder, value))
+        })
+    });
+    Ok(())
+}
+
+fn ask(name: &str, parameter: &Parameter) -> Result<String> {
+    if !std::io::stdin().is_terminal() {
+        return parameter.default.clone()
+            .ok_or_else(|| eyre!("parameter '{name}' has no default value, pass it with --set {name}=VALUE"));
+    }
+
+    print!("{name}");
+    if let Some(description) = &parameter.description {
+        print!(" ({description})");
+    }
+    if let Some(default) = &parameter.default {
+        print!(" [{default}]");
+    }
+    print!(": ");
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    match (answer.trim(), &parameter.default) {
+        ("", Some(default)) => Ok(default.clone()),
+        ("", None) => bail!("parameter '{name}' is required"),
+        (answer, _) => Ok(answer.to_owned()),
+    }
+}
@@ -0,0 +1,142 @@
+//! Timeline of changes reconstructed from backups. Every backup holds
+//! settings as they were before change, so comparing consecutive backups
+//! shows what changed between them, whether it was logi-man, Options+ UI
+//! or agent itself.
+
+use std::{collections::{BTreeMap, BTreeSet}, path::Path};
+
+use eyre::Result;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::{actions, backup, models::{Assignment, Profile, Settings}};
+
+pub fn timeline(db_path: &Path, device: Option<&str>, since: Option<&str>) -> Result<()> {
+    let mut snapshots = backup::snapshots(db_path)?;
+    if snapshots.is_empty() {
+        println!("No backups of {} found", db_path.display());
+        return Ok(());
+    }
+    // The last backup made before `since` is kept to compare the next one with.
+    if let Some(since) = since {
+        let first = snapshots.iter().position(|(time, _)| time.as_str() >= since).unwrap_or(snapshots.len());
+        snapshots.drain(..first.saturating_sub(1));
+    }
+
+    let mut previous: Option<(String, Settings)> = None;
+    let mut changed = false;
+    for (time, file) in snapshots {
+        let settings = match backup::read_settings(&file).and_then(|json| Ok(serde_json::from_slice(&json)?)) {
+            Ok(settings) => settings,
+            Err(err) => {
+                warn!(file = %file.display(), "skipping unreadable backup: {err:#}");
+                continue;
+            }
+        };
+        if let Some((previous_time, previous)) = &previous {
+            changed |= print_changes(previous_time, &time, previous, &settings, device);
+        }
+        previous = Some((time, settings));
+    }
+
+    // Changes made after the last backup are in database itself.
+    let db = crate::open_database(db_path, true)?;
+    let settings: Settings = serde_json::from_slice(&crate::load_settings(&db)?)?;
+    if let Some((previous_time, previous)) = &previous {
+        changed |= print_changes(previous_time, "now", previous, &settings, device);
+    }
+    if !changed {
+        println!("No changes found");
+    }
+    Ok(())
+}
+
+/// Prints changes between two snapshots, returns whether there are any.
+fn print_changes(from: &str, to: &str, old: &Settings, new: &Settings, device: Option<&str>) -> bool {
+    let changes = changes(old, new, device);
+    if !changes.is_empty() {
+        println!("{from} .. {to}:");
+        for change in &changes {
+            println!("  {change}");
+        }
+    }
+    !changes.is_empty()
+}
+
+/// Describes changes of devices, profiles, assignments and device settings.
+fn changes(old: &Settings, new: &Settings, device: Option<&str>) -> Vec<String> {
+    let mut changes = Vec::new();
+    let selected = |slot_prefix: &str| device.is_none_or(|device| device == slot_prefix);
+
+    let devices = |settings: &Settings| -> BTreeSet<String> {
+        settings.known_devices().into_iter().map(|device| device.slot_prefix.clone()).collect()
+    };
+    let (old_devices, new_devices) = (devices(old), devices(new));
+    for added in new_devices.difference(&old_devices).filter(|d| selected(d)) {
+        changes.push(format!("device {added} added"));
+    }
+    for removed in old_devices.difference(&new_devices).filter(|d| selected(d)) {
+        changes.push(format!("device {removed} removed"));
+    }
+    let all_devices: BTreeSet<&String> = old_devices.union(&new_devices).filter(|d| selected(d)).collect();
+
+    let profiles = |settings: &Settings| -> BTreeMap<String, Profile> {
+        settings.profiles.values().map(|profile| (profile.display_name().to_owned(), profile.clone())).collect()
+    };
+    let (old_profiles, new_profiles) = (profiles(old), profiles(new));
+    // With device given, profiles are only reported if they have something for it.
+    let relevant = |profile: &Profile| device.is_none_or(|device| {
+        profile.assignments.iter().any(|a| a.device() == Some(device)) || profile.device_settings(device).next().is_some()
+    });
+    for (name, profile) in &new_profiles {
+        if !old_profiles.contains_key(name) && relevant(profile) {
+            changes.push(format!("profile {name} added with {} assignment(s)", profile.assignments.len()));
+        }
+    }
+    for (name, profile) in &old_profiles {
+        let Some(new_profile) = new_profiles.get(name) else {
+            if relevant(profile) {
+                changes.push(format!("profile {name} removed"));
+            }
+            continue;
+        };
+
+        let assignments = |profile: &Profile| -> BTreeMap<String, Assignment> {
+            profile.assignments.iter()
+                .filter(|a| a.device().is_some_and(selected))
+                .map(|a| (a.slot_id.clone(), a.clone()))
+                .collect()
+        };
+        let (old_assignments, new_assignments) = (assignments(profile), assignments(new_profile));
+        let slot_ids: BTreeSet<&String> = old_assignments.keys().chain(new_assignments.keys()).collect();
+        for slot_id in slot_ids {
+            match (old_assignments.get(slot_id), new_assignments.get(slot_id)) {
+                (Some(a), None) => changes.push(format!("{name}: {slot_id} unassigned, was {}", actions::describe_assignment(a, old))),
+                (None, Some(b)) => changes.push(format!("{name}: {slot_id} assigned {}", actions::describe_assignment(b, new))),
+                (Some(a), Some(b)) if a != b => {
+                    let (before, after) = (actions::describe_assignment(a, old), actions::describe_assignment(b, new));
+                    if before == after {
+                        changes.push(format!("{name}: {slot_id} {after} changed"));
+                    } else {
+                        changes.push(format!("{name}: {slot_id} {before} -> {after}"));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for device in &all_devices {
+            let old_settings: BTreeMap<&str, &Value> = profile.device_settings(device).collect();
+            let new_settings: BTreeMap<&str, &Value> = new_profile.device_settings(device).collect();
+            let names: BTreeSet<&str> = old_settings.keys().chain(new_settings.keys()).copied().collect();
+            for setting in names {
+                let (before, after) = (old_settings.get(setting), new_settings.get(setting));
+                if before != after {
+                    let show = |value: Option<&&Value>| value.map_or("unset".to_owned(), |value| value.to_string());
+                    changes.push(format!("{name}: {device} {setting} {} -> {}", show(before), show(after)));
+                }
+            }
+        }
+    }
+    changes
+}
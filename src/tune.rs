@@ -0,0 +1,58 @@
+use std::path::Path;
+
+use eyre::{bail, Result};
+use serde_json::{json, Value};
+
+use crate::{models::Settings, options::{Toggle, Tune}, resolve};
+
+/// Per-device settings keys, without device slot prefix. They are named
+/// after Options+ UI, but aren't confirmed for every device, so only keys
+/// Options+ has already written for device are changed.
+const DPI: &str = "dpi";
+const SMARTSHIFT_THRESHOLD: &str = "smartshift_threshold";
+const NATURAL_SCROLL: &str = "natural_scroll";
+
+pub fn tune(db_path: &Path, opts: Tune, db: rusqlite::Connection, old_settings: Vec<u8>) -> Result<()> {
+    let mut settings: Settings = serde_json::from_slice(&old_settings)?;
+    let natural_scroll = opts.natural_scroll.map(|toggle| matches!(toggle, Toggle::On));
+    let changes = changes(opts.dpi, opts.smartshift_threshold, natural_scroll);
+
+    if changes.is_empty() {
+        resolve::device(&settings, &opts.device)?;
+        let profile = resolve::profile(&settings, &opts.profile)?.1;
+        for (setting, value) in profile.device_settings(&opts.device) {
+            println!("{setting}: {value}");
+        }
+        return Ok(());
+    }
+
+    apply(&mut settings, &opts.device, &opts.profile, changes)?;
+    crate::write_settings(db_path, &db, &old_settings, &settings, opts.dry_run)
+}
+
+/// Returns device settings to change with their new values.
+pub fn changes(dpi: Option<u32>, smartshift_threshold: Option<u32>, natural_scroll: Option<bool>) -> Vec<(&'static str, Value)> {
+    [
+        dpi.map(|dpi| (DPI, json!(dpi))),
+        smartshift_threshold.map(|threshold| (SMARTSHIFT_THRESHOLD, json!(threshold))),
+        natural_scroll.map(|on| (NATURAL_SCROLL, json!(on))),
+    ].into_iter().flatten().collect()
+}
+
+/// Changes device settings in profile, printing old and new values.
+/// Settings device doesn't have in any profile are refused.
+pub fn apply(settings: &mut Settings, device: &str, profile: &str, changes: Vec<(&str, Value)>) -> Result<()> {
+    resolve::device(settings, device)?;
+    for (setting, _) in &changes {
+        if !settings.profiles.values().any(|profile| profile.device_settings(device).any(|(s, _)| s == *setting)) {
+            bail!("{device} has no {setting} setting, it may be named differently for this device; change it in Options+ once, so that it is stored");
+        }
+    }
+    let key = resolve::profile(settings, profile)?.0.clone();
+    let profile = settings.profiles.get_mut(&key).unwrap();
+    for (setting, value) in changes {
+        let old = profile.rest.insert(format!("{device}_{setting}"), value.clone());
+        println!("{setting}: {} -> {value}", old.as_ref().map_or("unset".to_owned(), Value::to_string));
+    }
+    Ok(())
+}
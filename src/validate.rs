@@ -0,0 +1,109 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use eyre::{bail, Result};
+use serde_json::Value;
+
+use crate::{blob, models::Settings};
+
+/// Checks database and settings, printing every problem found.
+pub fn validate(db: &rusqlite::Connection) -> Result<()> {
+    let mut problems = Vec::new();
+    check(db, &mut problems)?;
+
+    if problems.is_empty() {
+        println!("Settings are valid");
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("problem: {problem}");
+    }
+    bail!("found {} problem(s)", problems.len());
+}
+
+fn check(db: &rusqlite::Connection, problems: &mut Vec<String>) -> Result<()> {
+    // Table shape, the same invariants `load_settings` relies on.
+    let columns: BTreeSet<String> = db.prepare("SELECT name FROM pragma_table_info('data')")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    if columns.is_empty() {
+        problems.push("there is no 'data' table".to_owned());
+        return Ok(());
+    }
+    for column in ["_id", "file"] {
+        if !columns.contains(column) {
+            problems.push(format!("'data' table has no '{column}' column"));
+            return Ok(());
+        }
+    }
+    let ids: Vec<i64> = db.prepare("SELECT _id FROM data")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    if ids != [1] {
+        problems.push(format!("'data' table is expected to contain single row with _id=1, but it contains rows {ids:?}"));
+        if !ids.contains(&1) {
+            return Ok(());
+        }
+    }
+
+    let blob: Vec<u8> = db.query_row("SELECT file FROM data WHERE _id=1", [], |row| row.get(0))?;
+    let (_, json) = blob::unwrap(&blob);
+    let raw: Value = match serde_json::from_slice(json) {
+        Ok(raw) => raw,
+        Err(err) => {
+            problems.push(format!("settings aren't valid JSON: {err}"));
+            return Ok(());
+        }
+    };
+
+    // Profile list and profile objects must match.
+    let profile_keys: Vec<&str> = raw.get("profile_keys").and_then(Value::as_array).into_iter().flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    let mut seen = BTreeSet::new();
+    for key in &profile_keys {
+        if !seen.insert(key) {
+            problems.push(format!("profile '{key}' is listed in profile_keys several times"));
+        }
+        if !raw.get(key).is_some_and(Value::is_object) {
+            problems.push(format!("profile '{key}' is listed in profile_keys, but there is no such profile"));
+        }
+    }
+    for (key, value) in raw.as_object().into_iter().flatten() {
+        let looks_like_profile = value.get("assignments").is_some_and(Value::is_array);
+        if looks_like_profile && !profile_keys.contains(&key.as_str()) {
+            problems.push(format!("profile '{key}' isn't listed in profile_keys"));
+        }
+    }
+
+    let settings: Settings = match serde_json::from_value(raw) {
+        Ok(settings) => settings,
+        Err(err) => {
+            problems.push(format!("settings have unexpected structure: {err}"));
+            return Ok(());
+        }
+    };
+
+    let mut devices: BTreeMap<&str, usize> = BTreeMap::new();
+    for device in &settings.ever_connected_devices.devices {
+        *devices.entry(device.slot_prefix.as_str()).or_default() += 1;
+    }
+    for (device, count) in &devices {
+        if *count > 1 {
+            problems.push(format!("device '{device}' is listed {count} times"));
+        }
+    }
+
+    for key in &settings.profile_keys {
+        let Some(profile) = settings.profiles.get(key) else { continue };
+        for assignment in &profile.assignments {
+            match assignment.device() {
+                Some(device) if devices.contains_key(device) => {}
+                Some(device) => problems.push(format!(
+                    "profile '{}' has assignment '{}' for unknown device '{device}'", profile.display_name(), assignment.slot_id)),
+                None => problems.push(format!(
+                    "profile '{}' has assignment with malformed slot id '{}'", profile.display_name(), assignment.slot_id)),
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+//! Watching for settings changes made by others: Options+ UI, agent itself
+//! or other logi-man invocations. Changes are recorded into history, so it
+//! is possible to find out later when and why settings drifted.
+
+use std::path::Path;
+
+use eyre::Result;
+use serde::Serialize;
+
+use crate::{
+    agent, hooks,
+    journal::{self, HistoryStore, NewChange, SqliteHistory},
+    options::Watch,
+};
+
+/// Change noticed by watch, stored as change details in history.
+#[derive(Serialize)]
+struct Event<'a> {
+    /// Inferred cause of change.
+    cause: &'a str,
+    /// JSON pointers to changed values.
+    paths: Vec<String>,
+}
+
+pub fn watch(db_path: &Path, opts: Watch, db: &rusqlite::Connection, settings: Vec<u8>) -> Result<()> {
+    let history = SqliteHistory::open_default()?;
+    let mut last = settings;
+    // Agent isn't seen running between polls when it is restarted.
+    let mut agent_stopped = false;
+    println!("Watching {}, press Ctrl-C to stop", db_path.display());
+    loop {
+        std::thread::sleep(opts.interval);
+        // Agent status isn't available on some platforms, don't guess then.
+        agent_stopped |= agent::is_running().is_ok_and(|running| !running);
+
+        // Database may be locked while agent writes it, try on next poll.
+        let settings = match crate::load_settings(db) {
+            Ok(settings) => settings,
+            Err(err) => {
+                tracing::warn!("can't load settings: {err}");
+                continue;
+            }
+        };
+        if settings == last {
+            continue;
+        }
+        let differences = hooks::diff(&serde_json::from_slice(&last)?, &serde_json::from_slice(&settings)?);
+        let paths: Vec<String> = differences.into_iter().map(|d| d.pointer).collect();
+        let cause = infer_cause(&history, db_path, &settings, &paths, agent_stopped)?;
+        let now: String = db.query_row("SELECT strftime('%H:%M:%S', 'now', 'localtime')", [], |row| row.get(0))?;
+        println!("[{now}] {cause}: {}", paths.join(", "));
+
+        let command = format!("{cause}, {} value(s) changed", paths.len());
+        let details = serde_json::to_string(&Event { cause, paths })?;
+        history.record(&NewChange { db_path, source: "watch", command, before: &last, after: &settings, details: Some(details) })?;
+
+        last = settings;
+        agent_stopped = false;
+    }
+}
+
+fn infer_cause(history: &SqliteHistory, db_path: &Path, settings: &[u8], paths: &[String], agent_stopped: bool) -> Result<&'static str> {
+    // logi-man records its own changes, find out whether this is one of them.
+    let checksum = journal::checksum(settings);
+    if history.log(db_path, Some("cli"), 1)?.first().is_some_and(|change| change.checksum_after == checksum) {
+        return Ok("logi-man");
+    }
+    if agent_stopped {
+        return Ok("agent restart");
+    }
+    // Agent updates device list itself when device connects.
+    let device_related = |path: &String| path.starts_with("/ever_connected_devices") || path.starts_with("/migration_report");
+    if paths.iter().all(device_related) {
+        return Ok("device connection");
+    }
+    Ok("GUI edit")
+}
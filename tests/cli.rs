@@ -0,0 +1,1009 @@
+mod common;
+
+use common::{slot_ids, Fixture, SETTINGS};
+use logi_man::models::Settings;
+use serde_json::Value;
+
+const MX3S: &str = "mx-master-3s-2b034";
+const MX3: &str = "mx-master-3-6b023";
+
+fn original() -> Value {
+    serde_json::from_str(SETTINGS).unwrap()
+}
+
+#[test]
+fn model_preserves_unknown_fields() {
+    let settings: Settings = serde_json::from_str(SETTINGS).unwrap();
+    assert_eq!(serde_json::to_value(&settings).unwrap(), original());
+}
+
+#[test]
+fn model_round_trip_is_byte_for_byte() {
+    // Formatting isn't kept, so settings are compared in compact form, the
+    // same way they are stored by Options+.
+    let compact = serde_json::to_string(&original()).unwrap();
+    let settings: Settings = serde_json::from_str(&compact).unwrap();
+    assert_eq!(serde_json::to_string(&settings).unwrap(), compact);
+}
+
+#[test]
+fn transfer_copies_assignments_in_all_profiles() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["transfer-assignments", MX3S, MX3]).assert().success();
+
+    let settings = fixture.settings_json();
+    for profile in ["profile-default", "profile-safari", "profile-empty"] {
+        let expected: Vec<String> = slot_ids(&settings, profile, MX3S).iter().map(|s| s.replace(MX3S, MX3)).collect();
+        assert_eq!(slot_ids(&settings, profile, MX3), expected, "{profile}");
+    }
+    assert_eq!(fixture.backups().len(), 1);
+}
+
+#[test]
+fn transfer_duplicates_bound_smart_actions() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["transfer-assignments", MX3S, MX3]).assert().success();
+
+    let settings = fixture.settings_json();
+    let copy = settings["smart_actions"].as_array().unwrap().iter()
+        .find(|action| action["name"] == "Gesture combo (mx-master-3-6b023)")
+        .expect("Smart Action is copied");
+    assert_eq!(copy["trigger"]["slotId"], "mx-master-3-6b023_c195");
+    let assignment = settings["profile-default"]["assignments"].as_array().unwrap().iter()
+        .find(|a| a["slotId"] == "mx-master-3-6b023_c195")
+        .unwrap();
+    assert_eq!(assignment["card"]["macro"]["smartActionId"], copy["id"]);
+}
+
+#[test]
+fn transfer_keeps_unknown_fields() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["transfer-assignments", MX3S, MX3]).assert().success();
+
+    let (before, after) = (original(), fixture.settings_json());
+    for pointer in ["/unknown_key", "/profile-default/unknownProfileField", "/ever_connected_devices",
+                    "/migration_report", "/flow", "/profile-default/assignments/1"] {
+        assert_eq!(after.pointer(pointer), before.pointer(pointer), "{pointer}");
+    }
+    let copied = after["profile-default"]["assignments"].as_array().unwrap().iter()
+        .find(|a| a["slotId"] == "mx-master-3-6b023_c83")
+        .unwrap();
+    assert_eq!(copied["unknownAssignmentField"], "kept");
+    assert_eq!(copied["card"]["unknownCardField"], serde_json::json!([1, 2]));
+    assert_eq!(copied["card"]["macro"]["unknownMacroField"], 1);
+}
+
+#[test]
+fn transfer_dry_run_changes_nothing() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["transfer-assignments", MX3S, MX3, "--dry-run"]).assert().success();
+
+    assert_eq!(fixture.settings(), SETTINGS.as_bytes());
+    assert!(fixture.backups().is_empty());
+}
+
+#[test]
+fn read_only_rejects_changes() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["--read-only", "transfer-assignments", MX3S, MX3]).assert().failure();
+
+    assert_eq!(fixture.settings(), SETTINGS.as_bytes());
+}
+
+#[test]
+fn show_settings_prints_them_as_stored() {
+    let fixture = Fixture::new();
+    fixture.cmd().arg("show-settings").assert().success().stdout(SETTINGS);
+}
+
+#[test]
+fn show_settings_prints_them_as_yaml() {
+    let fixture = Fixture::new();
+    let output = fixture.cmd().args(["show-settings", "--yaml"]).assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.starts_with("profile_keys:\n- profile-default\n"), "{output}");
+    assert!(output.contains("unknown_key:\n  z: 1\n  a: 2\n"), "{output}");
+}
+
+#[test]
+fn edit_stores_input_byte_for_byte() {
+    let fixture = Fixture::new();
+    let edited = SETTINGS.replace(r#""name": "Default""#, r#""name": "Main""#);
+    fixture.cmd().args(["edit-settings", "--stdin"]).write_stdin(edited.clone()).assert().success();
+
+    assert_eq!(fixture.settings(), edited.as_bytes());
+    assert_eq!(fixture.backups().len(), 1);
+}
+
+#[test]
+fn edit_rejects_invalid_settings() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["edit-settings", "--stdin"]).write_stdin(r#"{"profile_keys": []}"#).assert().failure();
+
+    assert_eq!(fixture.settings(), SETTINGS.as_bytes());
+    assert!(fixture.backups().is_empty());
+}
+
+#[test]
+fn export_then_import_restores_profile() {
+    let source = Fixture::new();
+    let exported = source.path("exported.json");
+    source.cmd().arg("export").arg(&exported).assert().success();
+
+    let mut settings = original();
+    settings.as_object_mut().unwrap().remove("profile-safari");
+    settings["profile_keys"].as_array_mut().unwrap().retain(|key| key != "profile-safari");
+    let target = Fixture::with_settings(&serde_json::to_vec(&settings).unwrap());
+    target.cmd().arg("import").arg(&exported).assert().success();
+
+    let imported = target.settings_json();
+    let safari = imported["profile_keys"].as_array().unwrap().iter()
+        .map(|key| &imported[key.as_str().unwrap()])
+        .find(|profile| profile["applicationId"] == "com.apple.Safari")
+        .expect("Safari profile is imported");
+    assert_eq!(safari["assignments"], original()["profile-safari"]["assignments"]);
+    assert_eq!(imported["unknown_key"], original()["unknown_key"]);
+}
+
+#[test]
+fn import_is_idempotent() {
+    let fixture = Fixture::new();
+    let exported = fixture.path("exported.json");
+    fixture.cmd().arg("export").arg(&exported).assert().success();
+    fixture.cmd().arg("import").arg(&exported).assert().success();
+
+    assert_eq!(fixture.settings(), SETTINGS.as_bytes());
+    assert!(fixture.backups().is_empty());
+}
+
+#[test]
+fn profile_is_resolved_by_its_name() {
+    let fixture = Fixture::new();
+    let output = fixture.cmd().args(["profile", "show", "Safari"]).assert().success().get_output().stdout.clone();
+    assert!(String::from_utf8(output).unwrap().contains("Application: com.apple.Safari"));
+
+    let output = fixture.cmd().args(["profile", "show", "Safary"]).assert().failure().get_output().stderr.clone();
+    assert!(String::from_utf8(output).unwrap().contains("did you mean 'Safari'?"));
+}
+
+#[test]
+fn restore_captured_device_under_new_slot_prefix() {
+    const REPAIRED: &str = "mx-master-3s-2b034-2";
+    let mut settings = original();
+    let devices = settings["ever_connected_devices"]["devices"].as_array_mut().unwrap();
+    devices.push(serde_json::json!({"deviceModel": "2b034", "deviceType": "MOUSE", "slotPrefix": REPAIRED}));
+    // Target device has Smart Action of its own in profile missing in capture.
+    settings["smart_actions"].as_array_mut().unwrap()
+        .push(serde_json::json!({"id": "sa-3", "name": "Old", "trigger": {"slotId": format!("{REPAIRED}_c195")}, "steps": []}));
+    settings["profile_keys"].as_array_mut().unwrap().push("profile-mail".into());
+    settings["profile-mail"] = serde_json::json!({"applicationId": "com.apple.Mail", "assignments": [
+        {"slotId": format!("{REPAIRED}_c195"), "card": {"macro": {"type": "SMART_ACTION", "smartActionId": "sa-3"}}},
+    ]});
+    let fixture = Fixture::with_settings(&serde_json::to_vec(&settings).unwrap());
+    let captured = fixture.path("captured.json");
+    fixture.cmd().args(["capture", "--device", MX3S, "--output"]).arg(&captured).assert().success();
+    fixture.cmd().arg("restore").arg(&captured).args(["--device", REPAIRED]).assert().success();
+
+    let settings = fixture.settings_json();
+    let expected: Vec<String> = slot_ids(&settings, "profile-default", MX3S).iter().map(|s| s.replace(MX3S, REPAIRED)).collect();
+    assert_eq!(slot_ids(&settings, "profile-default", REPAIRED), expected);
+    let copy = settings["smart_actions"].as_array().unwrap().iter()
+        .find(|action| action["trigger"]["slotId"] == format!("{REPAIRED}_c195"))
+        .expect("Smart Action is restored");
+    let assignment = settings["profile-default"]["assignments"].as_array().unwrap().iter()
+        .find(|a| a["slotId"] == format!("{REPAIRED}_c195"))
+        .unwrap();
+    assert_eq!(assignment["card"]["macro"]["smartActionId"], copy["id"]);
+    assert!(!settings["smart_actions"].as_array().unwrap().iter().any(|action| action["id"] == "sa-3"));
+    assert_eq!(settings["profile-mail"]["assignments"], serde_json::json!([]));
+
+    fixture.cmd().arg("restore").arg(&captured).args(["--device", MX3]).assert().failure();
+}
+
+#[test]
+fn transfer_rejects_unknown_device() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["transfer-assignments", "mx-master-3s-2b03", MX3S]).assert().failure();
+    assert_eq!(fixture.settings(), SETTINGS.as_bytes());
+}
+
+#[test]
+fn transfer_accepts_keyboards_and_removed_devices() {
+    const KEYBOARD: &str = "mx-keys-s-b378";
+    let mut settings = original();
+    let devices = settings["ever_connected_devices"]["devices"].as_array_mut().unwrap();
+    devices.push(serde_json::json!({"deviceModel": "b378", "deviceType": "KEYBOARD", "slotPrefix": KEYBOARD}));
+    // Record of MX Master 3 is removed, its assignments are left.
+    devices.retain(|device| device["slotPrefix"] != MX3);
+    let fixture = Fixture::with_settings(&serde_json::to_vec(&settings).unwrap());
+    fixture.cmd().args(["transfer-assignments", MX3, KEYBOARD]).assert().success();
+
+    let settings = fixture.settings_json();
+    assert_eq!(slot_ids(&settings, "profile-default", KEYBOARD), [format!("{KEYBOARD}_c82")]);
+    let output = fixture.cmd().arg("list-devices").assert().success().get_output().stdout.clone();
+    assert!(!String::from_utf8(output).unwrap().contains(KEYBOARD));
+}
+
+#[test]
+fn zero_backup_age_is_rejected() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["--max-backup-age", "0", "transfer-assignments", MX3S, MX3]).assert().failure();
+    assert_eq!(fixture.settings(), SETTINGS.as_bytes());
+    assert!(fixture.backups().is_empty());
+}
+
+#[test]
+fn changes_within_one_second_are_backed_up_separately() {
+    let fixture = Fixture::new();
+    for dpi in ["1000", "1200", "1400"] {
+        fixture.cmd().args(["tune", "--device", MX3S, "--dpi", dpi]).assert().success();
+    }
+    assert_eq!(fixture.backups().len(), 3);
+}
+
+#[test]
+fn pruning_keeps_backup_just_made() {
+    let fixture = Fixture::new();
+    let old = fixture.path("settings.db.2000-01-01_00-00-00");
+    std::fs::write(&old, "").unwrap();
+    fixture.cmd().args(["--keep-backups", "1", "transfer-assignments", MX3S, MX3]).assert().success();
+    let backups = fixture.backups();
+    assert_eq!(backups.len(), 1);
+    assert_ne!(backups[0], old);
+}
+
+#[test]
+fn pruning_orders_backups_by_time_in_name() {
+    let fixture = Fixture::new();
+    // Copied backup gets new modification time, it is still the oldest one.
+    let old = fixture.path("settings.db.2000-01-01_00-00-00");
+    let file = std::fs::File::create(&old).unwrap();
+    file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(24 * 60 * 60)).unwrap();
+    fixture.cmd().args(["--keep-backups", "1", "transfer-assignments", MX3S, MX3]).assert().success();
+    let backups = fixture.backups();
+    assert_eq!(backups.len(), 1);
+    assert_ne!(backups[0], old);
+}
+
+#[test]
+fn gestures_set_keeps_unknown_fields_of_other_directions() {
+    let mut settings = original();
+    settings["profile-default"]["assignments"].as_array_mut().unwrap().push(serde_json::json!({
+        "slotId": "mx-master-3-6b023_c195",
+        "card": {"macro": {"type": "GESTURE", "gestures": {
+            "up": {"type": "OS_SHORTCUT", "shortcut": "MISSION_CONTROL", "extraField": 42},
+        }}},
+    }));
+    let fixture = Fixture::with_settings(&serde_json::to_vec(&settings).unwrap());
+    fixture.cmd().args(["gestures", "set", "--device", MX3, "--direction", "down", "--action", "app-expose"]).assert().success();
+
+    let settings = fixture.settings_json();
+    let assignment = settings["profile-default"]["assignments"].as_array().unwrap().iter()
+        .find(|a| a["slotId"] == "mx-master-3-6b023_c195")
+        .unwrap();
+    let gestures = &assignment["card"]["macro"]["gestures"];
+    assert_eq!(gestures["up"]["extraField"], 42);
+    assert_eq!(gestures["down"]["shortcut"], "APP_EXPOSE");
+}
+
+#[test]
+fn gestures_set_none_clears_direction() {
+    let mut settings = original();
+    settings["profile-default"]["assignments"].as_array_mut().unwrap().push(serde_json::json!({
+        "slotId": "mx-master-3-6b023_c195",
+        "card": {"macro": {"type": "GESTURE", "gestures": {
+            "up": {"type": "OS_SHORTCUT", "shortcut": "MISSION_CONTROL"},
+            "down": {"type": "OS_SHORTCUT", "shortcut": "APP_EXPOSE"},
+        }}},
+    }));
+    let fixture = Fixture::with_settings(&serde_json::to_vec(&settings).unwrap());
+    fixture.cmd().args(["gestures", "set", "--device", MX3, "--direction", "up", "--action", "none"]).assert().success();
+
+    let settings = fixture.settings_json();
+    let assignment = settings["profile-default"]["assignments"].as_array().unwrap().iter()
+        .find(|a| a["slotId"] == "mx-master-3-6b023_c195")
+        .unwrap();
+    assert_eq!(assignment["card"]["macro"]["gestures"], serde_json::json!({"down": {"type": "OS_SHORTCUT", "shortcut": "APP_EXPOSE"}}));
+    let output = fixture.cmd().args(["gestures", "show", "--device", MX3]).assert().success().get_output().stdout.clone();
+    assert!(String::from_utf8(output).unwrap().contains("up     default"));
+    fixture.cmd().args(["gestures", "show", "--device", "mx-master-3-6b02"]).assert().failure();
+}
+
+#[test]
+fn plan_with_misspelled_step_field_is_rejected() {
+    let fixture = Fixture::new();
+    let plan = fixture.path("plan.json");
+    let steps = serde_json::json!({"steps": [{"tune": {"device": MX3S, "dpi": 1200, "natrual_scroll": true}}]});
+    std::fs::write(&plan, steps.to_string()).unwrap();
+    fixture.cmd().arg("run").arg(&plan).assert().failure();
+    assert_eq!(fixture.settings(), SETTINGS.as_bytes());
+}
+
+#[test]
+fn yaml_plan_is_applied() {
+    let fixture = Fixture::new();
+    let plan = fixture.path("plan.yaml");
+    std::fs::write(&plan, format!("steps:\n  - set-assignment: {{device: {MX3S}, button: c82, action: mission-control}}\n")).unwrap();
+    fixture.cmd().arg("run").arg(&plan).assert().success();
+    let assignment = &fixture.settings_json()["profile-default"]["assignments"][0];
+    assert_eq!(assignment["card"]["macro"], serde_json::json!({"type": "OS_SHORTCUT", "shortcut": "MISSION_CONTROL"}));
+}
+
+#[test]
+fn plan_with_misspelled_action_is_rejected() {
+    let fixture = Fixture::new();
+    let plan = fixture.path("plan.json");
+    let steps = serde_json::json!({"steps": [{"set-assignment": {"device": MX3S, "button": "c82", "action": "mision-control"}}]});
+    std::fs::write(&plan, steps.to_string()).unwrap();
+    let output = fixture.cmd().arg("run").arg(&plan).assert().failure().get_output().stderr.clone();
+    assert!(String::from_utf8(output).unwrap().contains("did you mean 'mission-control'?"));
+    assert_eq!(fixture.settings(), SETTINGS.as_bytes());
+}
+
+#[test]
+fn invalid_polling_interval_is_rejected() {
+    let fixture = Fixture::new();
+    for interval in ["-1", "NaN", "inf", "0"] {
+        fixture.cmd().args(["query", "/unknown_key", "--watch", &format!("--interval={interval}")]).assert().failure().code(2);
+    }
+}
+
+#[test]
+fn too_large_age_is_rejected() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["forget-device", "--older-than", "99999999999999999w", "--yes"]).assert().failure().code(2);
+    assert_eq!(fixture.settings(), SETTINGS.as_bytes());
+}
+
+#[test]
+fn onboard_moves_assignments_to_other_buttons() {
+    let fixture = Fixture::new();
+    // Swap middle button and back, keep gesture button, drop Safari forward.
+    fixture.cmd().args(["onboard", MX3, "--from", MX3S]).write_stdin("back\nmiddle button\n\ndrop\n").assert().success();
+
+    let settings = fixture.settings_json();
+    let action = |slot_id: &str| settings["profile-default"]["assignments"].as_array().unwrap().iter()
+        .find(|a| a["slotId"] == slot_id)
+        .map(|a| a["card"]["macro"]["type"].clone());
+    assert_eq!(action("mx-master-3-6b023_c83"), Some("KEYSTROKE".into()));
+    assert_eq!(action("mx-master-3-6b023_c82"), Some("OS_SHORTCUT".into()));
+    assert_eq!(slot_ids(&settings, "profile-default", MX3).len(), 3);
+    assert!(slot_ids(&settings, "profile-safari", MX3).is_empty());
+}
+
+#[test]
+fn settings_with_leading_whitespace_are_loaded() {
+    let fixture = Fixture::with_settings(format!("\u{feff}\n  {SETTINGS}").as_bytes());
+    fixture.cmd().arg("list-devices").assert().success();
+    fixture.cmd().arg("validate").assert().success();
+    fixture.cmd().args(["edit-settings", "--stdin"]).write_stdin(format!("\n{SETTINGS}")).assert().success();
+    fixture.cmd().arg("list-devices").assert().success();
+}
+
+#[test]
+fn sync_dry_run_reports_conflicts_without_asking() {
+    let fixture = Fixture::new();
+    let mut settings = original();
+    settings["profile-default"]["assignments"][0]["card"]["macro"]["keystroke"]["code"] = 5.into();
+    let other = Fixture::with_settings(&serde_json::to_vec(&settings).unwrap());
+
+    let output = fixture.cmd().args(["sync", "--dry-run", "--with"]).arg(other.path("settings.db"))
+        .write_stdin("").assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    let (pull, push) = output.split_once("\nTo ").unwrap();
+    for side in [pull, push] {
+        assert!(side.contains("conflict:    default: mx-master-3s-2b034_c82"), "{side}");
+    }
+    assert_eq!(fixture.settings(), SETTINGS.as_bytes());
+}
+
+#[test]
+fn sync_writes_nothing_when_local_database_is_protected() {
+    let fixture = Fixture::new();
+    let mut settings = original();
+    // Both databases are changed: conflicting assignment is pulled, missing one is pushed.
+    settings["profile-default"]["assignments"][0]["card"]["macro"]["keystroke"]["code"] = 5.into();
+    settings["profile-default"]["assignments"].as_array_mut().unwrap().remove(1);
+    let other = Fixture::with_settings(&serde_json::to_vec(&settings).unwrap());
+    let config = fixture.path("home/.config/logi-man");
+    std::fs::create_dir_all(&config).unwrap();
+    let rule = std::fs::canonicalize(&fixture.db).unwrap();
+    std::fs::write(config.join("config.toml"), format!("protected_paths = [{:?}]\n", rule.to_str().unwrap())).unwrap();
+
+    fixture.cmd().args(["sync", "--prefer", "other", "--with"]).arg(&other.db).assert().failure();
+    assert_eq!(other.settings(), serde_json::to_vec(&settings).unwrap());
+    assert!(other.backups().is_empty());
+}
+
+#[test]
+fn legacy_import_translates_key_codes() {
+    let fixture = Fixture::new();
+    let legacy = fixture.path("options.json");
+    std::fs::write(&legacy, r#"{"applications": [{"bundleId": "com.apple.Mail", "buttons": [
+        {"cid": "0x53", "action": {"type": "Keystroke", "keyCode": 123, "modifiers": ["cmd"]}},
+        {"cid": "0x56", "action": {"type": "Keystroke", "keyCode": 200}}
+    ]}]}"#).unwrap();
+    let output = fixture.cmd().args(["import-legacy", MX3S, "--path"]).arg(&legacy).assert().success().get_output().stdout.clone();
+    assert!(String::from_utf8(output).unwrap().contains("unknown key code 200"));
+
+    let settings = fixture.settings_json();
+    let profile = settings.as_object().unwrap().values()
+        .find(|profile| profile["applicationId"] == "com.apple.Mail")
+        .unwrap();
+    assert_eq!(profile["assignments"][0]["card"]["macro"]["keystroke"], serde_json::json!({"code": 80, "modifiers": [227]}));
+    assert_eq!(profile["assignments"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn import_dry_run_reports_conflicts_without_asking() {
+    let fixture = Fixture::new();
+    let (exported, profile, action) = (fixture.path("exported.json"), fixture.path("profile.json"), fixture.path("action.json"));
+    fixture.cmd().arg("export").arg(&exported).assert().success();
+    fixture.cmd().args(["profile", "export", "default"]).arg(&profile).assert().success();
+    fixture.cmd().args(["smart-actions", "export", "Gesture combo"]).arg(&action).assert().success();
+    let code = r#""code": 4"#;
+    for file in [&exported, &profile] {
+        let text = std::fs::read_to_string(file).unwrap();
+        std::fs::write(file, text.replace(code, r#""code": 5"#)).unwrap();
+    }
+    let text = std::fs::read_to_string(&action).unwrap();
+    std::fs::write(&action, text.replace(r#""steps": []"#, r#""steps": [{}]"#)).unwrap();
+
+    let imports: [&[&str]; 3] = [&["import"], &["profile", "import"], &["smart-actions", "import"]];
+    for (args, file) in imports.into_iter().zip([&exported, &profile, &action]) {
+        let output = fixture.cmd().args(args).arg(file).args(["--on-conflict", "ask", "--dry-run"])
+            .write_stdin("").assert().success().get_output().stdout.clone();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("conflict:") && !output.contains("overwrite?"), "{args:?}: {output}");
+    }
+    assert_eq!(fixture.settings(), SETTINGS.as_bytes());
+}
+
+#[test]
+fn tune_changes_only_stored_settings() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["tune", "--device", MX3S, "--dpi", "1200"]).assert().success();
+    assert_eq!(fixture.settings_json()["profile-default"]["mx-master-3s-2b034_dpi"], 1200);
+
+    let changed = fixture.settings();
+    fixture.cmd().args(["tune", "--device", MX3S, "--dpi", "800", "--natural-scroll", "on"]).assert().failure();
+    fixture.cmd().args(["tune", "--device", MX3, "--dpi", "800"]).assert().failure();
+    assert_eq!(fixture.settings(), changed);
+}
+
+#[test]
+fn smart_action_import_renames_conflicting_one() {
+    let fixture = Fixture::new();
+    let file = fixture.path("action.json");
+    fixture.cmd().args(["smart-actions", "export", "Open work apps"]).arg(&file).assert().success();
+    let mut action: Value = serde_json::from_slice(&std::fs::read(&file).unwrap()).unwrap();
+    action["steps"][0]["app"] = "Mail".into();
+    std::fs::write(&file, serde_json::to_vec(&action).unwrap()).unwrap();
+
+    fixture.cmd().args(["smart-actions", "import"]).arg(&file).args(["--on-conflict", "rename"]).assert().success();
+    let settings = fixture.settings_json();
+    let actions = settings["smart_actions"].as_array().unwrap();
+    assert_eq!(actions[0], original()["smart_actions"][0]);
+    let renamed = actions.iter().find(|a| a["name"] == "Open work apps (2)").expect("action is renamed");
+    assert_eq!(renamed["steps"][0]["app"], "Mail");
+    assert_ne!(renamed["id"], "sa-1");
+}
+
+#[test]
+fn flow_reset_forgets_computers_only() {
+    let fixture = Fixture::new();
+    let output = fixture.cmd().args(["flow", "show"]).assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("work-mbp: edge LEFT") && output.contains("old-pc: edge RIGHT"), "{output}");
+
+    fixture.cmd().args(["flow", "reset"]).assert().success();
+    let (mut expected, settings) = (original(), fixture.settings_json());
+    expected["flow"]["computers"] = serde_json::json!([]);
+    assert_eq!(settings, expected);
+}
+
+#[test]
+fn template_parameters_are_substituted() {
+    let fixture = Fixture::new();
+    let file = fixture.path("profile.json");
+    std::fs::write(&file, serde_json::json!({
+        "$parameters": {
+            "browser": {"description": "Browser bundle id"},
+            "name": {"default": "Browser"},
+        },
+        "applicationId": "{browser}",
+        "name": "{name} ({browser})",
+        "assignments": [{"slotId": "mx-master-3s-2b034_c86", "card": {"id": "c4"}}],
+    }).to_string()).unwrap();
+
+    fixture.cmd().args(["profile", "import"]).arg(&file).write_stdin("").assert().failure();
+    fixture.cmd().args(["profile", "import"]).arg(&file).args(["--set", "browser=x", "--set", "other=y"])
+        .write_stdin("").assert().failure();
+    assert_eq!(fixture.settings(), SETTINGS.as_bytes());
+
+    fixture.cmd().args(["profile", "import"]).arg(&file).args(["--set", "browser=org.mozilla.firefox"])
+        .write_stdin("").assert().success();
+    let settings = fixture.settings_json();
+    let profile = settings.as_object().unwrap().values()
+        .find(|profile| profile["applicationId"] == "org.mozilla.firefox")
+        .expect("profile is imported");
+    assert_eq!(profile["name"], "Browser (org.mozilla.firefox)");
+    assert!(!profile.as_object().unwrap().contains_key("$parameters"));
+}
+
+/// Fixture settings with duplicate device record and assignment for device
+/// which isn't known.
+fn broken_settings() -> Value {
+    let mut settings = original();
+    let devices = settings["ever_connected_devices"]["devices"].as_array_mut().unwrap();
+    devices.push(devices[0].clone());
+    settings["profile-safari"]["assignments"].as_array_mut().unwrap()
+        .push(serde_json::json!({"slotId": "gone-1a2b3_c82", "card": {"id": "c10"}}));
+    settings
+}
+
+#[test]
+fn validate_reports_every_problem() {
+    let fixture = Fixture::new();
+    fixture.cmd().arg("validate").assert().success();
+
+    let mut settings = broken_settings();
+    settings["profile_keys"].as_array_mut().unwrap().pop();
+    let fixture = Fixture::with_settings(&serde_json::to_vec(&settings).unwrap());
+    let output = fixture.cmd().arg("validate").assert().failure().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    for problem in ["device 'mx-master-3s-2b034' is listed 2 times", "unknown device 'gone-1a2b3'",
+                    "profile 'profile-empty' isn't listed in profile_keys"] {
+        assert!(output.contains(problem), "{problem}: {output}");
+    }
+}
+
+#[test]
+fn repair_removes_duplicate_devices_and_orphaned_assignments() {
+    let fixture = Fixture::with_settings(&serde_json::to_vec(&broken_settings()).unwrap());
+    fixture.cmd().arg("repair").assert().failure();
+    let broken = fixture.settings();
+    fixture.cmd().args(["repair", "--dedupe-devices", "--remove-orphaned-assignments", "--dry-run"]).assert().success();
+    assert_eq!(fixture.settings(), broken);
+
+    fixture.cmd().args(["repair", "--dedupe-devices", "--remove-orphaned-assignments"]).assert().success();
+    // The last record of device is kept.
+    let mut expected = original();
+    let devices = expected["ever_connected_devices"]["devices"].as_array_mut().unwrap();
+    devices.rotate_left(1);
+    assert_eq!(fixture.settings_json(), expected);
+    fixture.cmd().arg("validate").assert().success();
+}
+
+#[test]
+fn forget_device_removes_every_trace_of_it() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["forget-device", MX3S, "--migration-report"]).assert().success();
+
+    // Smart Actions are kept, they may be bound to another device later.
+    let mut settings = fixture.settings_json();
+    assert_eq!(settings["smart_actions"], original()["smart_actions"]);
+    settings.as_object_mut().unwrap().remove("smart_actions");
+    let text = settings.to_string();
+    assert!(!text.contains(MX3S) && !text.contains("2b034"), "{text}");
+    assert_eq!(slot_ids(&settings, "profile-default", MX3), [format!("{MX3}_c82")]);
+}
+
+#[test]
+fn forget_device_selects_devices_by_filters() {
+    let mut settings = original();
+    let devices = &mut settings["ever_connected_devices"]["devices"];
+    devices[0]["lastConnected"] = "2000-01-01T00:00:00Z".into();
+    devices[1]["lastConnected"] = (std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+        .unwrap().as_millis() as u64).into();
+    let fixture = Fixture::with_settings(&serde_json::to_vec(&settings).unwrap());
+    let devices = |fixture: &Fixture| -> Vec<String> {
+        fixture.settings_json()["ever_connected_devices"]["devices"].as_array().unwrap().iter()
+            .map(|device| device["slotPrefix"].as_str().unwrap().to_owned())
+            .collect()
+    };
+
+    // Nothing is forgotten without confirmation.
+    fixture.cmd().args(["forget-device", "--unused"]).write_stdin("").assert().success();
+    assert_eq!(devices(&fixture), [MX3S, MX3, "virt"]);
+
+    fixture.cmd().args(["forget-device", "--unused", "--yes"]).assert().success();
+    assert_eq!(devices(&fixture), [MX3S, MX3]);
+    fixture.cmd().args(["forget-device", "--older-than", "1y", "--yes"]).assert().success();
+    assert_eq!(devices(&fixture), [MX3]);
+    fixture.cmd().args(["forget-device", "--type", "keyboard", "--yes"]).assert().success();
+    assert_eq!(devices(&fixture), [MX3]);
+    fixture.cmd().args(["forget-device", "--type", "mouse", "--yes"]).assert().success();
+    assert!(devices(&fixture).is_empty());
+}
+
+#[test]
+fn backup_verify_reports_bad_backups() {
+    let fixture = Fixture::new();
+    for dpi in ["1000", "1200"] {
+        fixture.cmd().args(["tune", "--device", MX3S, "--dpi", dpi]).assert().success();
+    }
+    std::fs::write(fixture.path("settings.db.2000-01-01_00-00-00"), b"not a database").unwrap();
+    let unparseable = Fixture::with_settings(b"not json");
+    std::fs::copy(&unparseable.db, fixture.path("settings.db.2000-01-02_00-00-00")).unwrap();
+
+    let output = fixture.cmd().args(["backup", "verify", "--all", "--jobs", "2"])
+        .assert().failure().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("2 ok, 1 corrupt, 1 unparseable"), "{output}");
+    assert!(output.contains("corrupt      ") && output.contains("settings.db.2000-01-01_00-00-00"), "{output}");
+
+    let good = fixture.backups().into_iter().filter(|backup| !backup.to_string_lossy().contains("2000-"));
+    fixture.cmd().args(["backup", "verify"]).args(good).assert().success();
+}
+
+#[test]
+fn compressed_backups_are_verified_and_read() {
+    let fixture = Fixture::new();
+    let config = fixture.path("home/.config/logi-man/config.toml");
+    std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+    std::fs::write(&config, "[json_backup]\nenabled = true\ncompress = true\n").unwrap();
+    fixture.cmd().args(["--compress-backups", "tune", "--device", MX3S, "--dpi", "1200"]).assert().success();
+
+    let backups = fixture.backups();
+    let names: Vec<String> = backups.iter().map(|b| b.file_name().unwrap().to_string_lossy().into_owned()).collect();
+    assert_eq!(names.len(), 2, "{names:?}");
+    assert!(names[0].ends_with(".gz") && names[1].ends_with(".json.gz"), "{names:?}");
+    fixture.cmd().args(["backup", "verify", "--all"]).assert().success();
+
+    let output = fixture.cmd().args(["history", "timeline"]).assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("dpi 1600 -> 1200"), "{output}");
+}
+
+#[test]
+fn timeline_lists_changes_between_backups() {
+    let fixture = Fixture::new();
+    let mut settings = original();
+    let backups = [("2025-04-01_10-00-00", settings.clone()), ("2025-04-02_10-00-00", {
+        settings["profile-default"]["mx-master-3s-2b034_dpi"] = 1000.into();
+        settings.clone()
+    }), ("2025-04-03_10-00-00", {
+        settings["profile-default"]["assignments"].as_array_mut().unwrap().remove(2);
+        settings.clone()
+    })];
+    for (time, settings) in backups {
+        let backup = Fixture::with_settings(&serde_json::to_vec(&settings).unwrap());
+        std::fs::copy(&backup.db, fixture.path(&format!("settings.db.{time}"))).unwrap();
+    }
+    let timeline = |args: &[&str]| {
+        let output = fixture.cmd().args(["history", "timeline"]).args(args).assert().success().get_output().stdout.clone();
+        String::from_utf8(output).unwrap()
+    };
+
+    assert_eq!(timeline(&["--device", MX3S]), "\
+2025-04-01 10:00:00 .. 2025-04-02 10:00:00:
+  default: mx-master-3s-2b034 dpi 1600 -> 1000
+2025-04-03 10:00:00 .. now:
+  default: mx-master-3s-2b034 dpi 1000 -> 1600
+");
+    let output = timeline(&["--since", "2025-04-03"]);
+    assert!(!output.contains("2025-04-01") && output.contains("mx-master-3-6b023_c82 assigned"), "{output}");
+    fixture.cmd().args(["history", "timeline", "--since", "April"]).assert().failure().code(2);
+}
+
+#[test]
+fn doctor_checks_database_access() {
+    let fixture = Fixture::new();
+    let output = fixture.cmd().arg("doctor").assert().success().get_output().stdout.clone();
+    assert!(String::from_utf8(output).unwrap().contains("No problems found"));
+
+    std::fs::remove_file(&fixture.db).unwrap();
+    let output = fixture.cmd().arg("doctor").assert().failure().get_output().stdout.clone();
+    assert!(String::from_utf8(output).unwrap().contains("[FAIL] database file exists"));
+}
+
+#[test]
+fn history_records_changes() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["tune", "--device", MX3S, "--dpi", "1200"]).assert().success();
+    fixture.cmd().args(["tune", "--device", MX3S, "--dpi", "1400", "--dry-run"]).assert().success();
+
+    let output = fixture.cmd().args(["history", "log"]).assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(output.lines().count(), 1, "{output}");
+    assert!(output.contains("cli") && output.contains("--dpi 1200"), "{output}");
+
+    let output = fixture.cmd().args(["history", "show", "1", "--settings"]).assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("\"mx-master-3s-2b034_dpi\": 1200"), "{output}");
+}
+
+#[test]
+fn device_ids_are_completed_from_database() {
+    let fixture = Fixture::new();
+    let script = fixture.cmd().args(["completions", "bash"]).assert().success().get_output().stdout.clone();
+    assert!(String::from_utf8(script).unwrap().contains("COMPLETE"));
+
+    // Completion request doesn't take options, it is answered before they are parsed.
+    let output = assert_cmd::cargo::cargo_bin_cmd!("logi-man")
+        .env("HOME", fixture.path("home"))
+        .env("XDG_CONFIG_HOME", fixture.path("home/.config"))
+        .env("LOGI_MAN_DB", &fixture.db)
+        .env("COMPLETE", "fish")
+        .args(["--", "logi-man", "transfer-assignments", "mx"])
+        .assert().success().get_output().stdout.clone();
+    assert_eq!(String::from_utf8(output).unwrap(), format!("{MX3}\tMX Master 3\n{MX3S}\tMX Master 3S\n"));
+}
+
+#[test]
+fn profile_copy_creates_profile_for_another_application() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["profile", "copy", "Safari", "org.mozilla.firefox"]).assert().success();
+    fixture.cmd().args(["profile", "copy", "Safari", "org.mozilla.firefox"]).assert().failure();
+
+    let settings = fixture.settings_json();
+    let keys = settings["profile_keys"].as_array().unwrap();
+    assert_eq!(keys.len(), 4);
+    let copy = &settings[keys[3].as_str().unwrap()];
+    assert_eq!(copy["applicationId"], "org.mozilla.firefox");
+    assert_eq!(copy["assignments"], original()["profile-safari"]["assignments"]);
+
+    let output = fixture.cmd().args(["profile", "list"]).assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.lines().any(|line| line.starts_with("org.mozilla.firefox: ") && line.contains("(1 assignments)")), "{output}");
+}
+
+#[test]
+fn profile_prune_empty_deletes_profiles_changing_nothing() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["profile", "prune-empty", "--dry-run"]).assert().success();
+    fixture.cmd().args(["profile", "prune-empty"]).write_stdin("").assert().success();
+    assert_eq!(fixture.settings(), SETTINGS.as_bytes());
+
+    fixture.cmd().args(["profile", "prune-empty", "--yes"]).assert().success();
+    let (mut expected, settings) = (original(), fixture.settings_json());
+    let object = expected.as_object_mut().unwrap();
+    object.remove("profile-empty");
+    object["profile_keys"].as_array_mut().unwrap().pop();
+    assert_eq!(settings, expected);
+}
+
+#[test]
+fn suggest_transfer_proposes_device_with_assignments() {
+    let mut settings = original();
+    settings["ever_connected_devices"]["devices"].as_array_mut().unwrap()
+        .push(serde_json::json!({"deviceModel": "2b034", "deviceType": "MOUSE", "slotPrefix": "mx-master-3s-2b035"}));
+    let fixture = Fixture::with_settings(&serde_json::to_vec(&settings).unwrap());
+    let output = fixture.cmd().arg("suggest-transfer").assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("logi-man transfer-assignments mx-master-3s-2b034 mx-master-3s-2b035"), "{output}");
+}
+
+#[test]
+fn status_summarizes_database() {
+    let fixture = Fixture::new();
+    let output = fixture.cmd().arg("status").assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("Profiles:     3") && output.contains("Devices:      3"), "{output}");
+    assert!(output.contains(&format!("Settings:     {} bytes", SETTINGS.len())), "{output}");
+}
+
+#[test]
+fn daily_snapshot_is_made_before_first_change_of_the_day() {
+    let fixture = Fixture::new();
+    let config = fixture.path("home/.config/logi-man/config.toml");
+    std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+    std::fs::write(&config, "daily_snapshot = true\n").unwrap();
+    for dpi in ["1000", "1200"] {
+        fixture.cmd().args(["tune", "--device", MX3S, "--dpi", dpi]).assert().success();
+    }
+
+    let daily: Vec<_> = fixture.backups().into_iter()
+        .filter(|backup| backup.to_string_lossy().contains(".daily."))
+        .collect();
+    assert_eq!(daily.len(), 1);
+    assert_eq!(fixture.backups().len(), 3);
+    let snapshot = Fixture::with_settings(b"");
+    std::fs::copy(&daily[0], &snapshot.db).unwrap();
+    assert_eq!(snapshot.settings(), SETTINGS.as_bytes());
+}
+
+#[test]
+fn query_prints_value_by_pointer() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["query", "/profile_keys/1"]).assert().success().stdout("\"profile-safari\"\n");
+    let output = fixture.cmd().args(["query", "/unknown_key"]).assert().success().get_output().stdout.clone();
+    assert_eq!(serde_json::from_slice::<Value>(&output).unwrap(), original()["unknown_key"]);
+    fixture.cmd().args(["query", "/missing"]).assert().failure();
+}
+
+#[test]
+fn schema_describes_stored_settings() {
+    let fixture = Fixture::new();
+    let output = fixture.cmd().arg("schema").assert().success().get_output().stdout.clone();
+    let schema: Value = serde_json::from_slice(&output).unwrap();
+
+    let settings = original();
+    for key in schema["required"].as_array().unwrap() {
+        assert!(settings.get(key.as_str().unwrap()).is_some(), "{key}");
+    }
+    // All references point to definitions.
+    let text = String::from_utf8(output).unwrap();
+    for reference in text.split("\"#/$defs/").skip(1) {
+        let name = &reference[..reference.find('"').unwrap()];
+        assert!(schema["$defs"].get(name).is_some(), "{name}");
+    }
+}
+
+#[test]
+fn profile_diff_against_default_decodes_actions() {
+    let fixture = Fixture::new();
+    let output = fixture.cmd().args(["profile", "diff-against-default", "Safari"]).assert().success().get_output().stdout.clone();
+    assert_eq!(String::from_utf8(output).unwrap(), format!("{MX3S}:\n  c86: default -> keystroke 5\n"));
+}
+
+#[test]
+fn verbose_logs_steps_and_sql_to_stderr() {
+    let fixture = Fixture::new();
+    let output = fixture.cmd().args(["-v", "tune", "--device", MX3S, "--dpi", "1000"]).assert().success().get_output().clone();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("backup created") && !stderr.contains("sql="), "{stderr}");
+    assert!(!String::from_utf8(output.stdout).unwrap().contains("backup created"));
+
+    let output = fixture.cmd().args(["-vv", "tune", "--device", MX3S, "--dpi", "1200"]).assert().success().get_output().stderr.clone();
+    assert!(String::from_utf8(output).unwrap().contains("sql=\"SELECT _id, file FROM data\""));
+}
+
+#[test]
+fn settings_json_is_saved_with_backup() {
+    let fixture = Fixture::new();
+    let config = fixture.path("home/.config/logi-man/config.toml");
+    std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+    std::fs::write(&config, "[json_backup]\nenabled = true\ncanonical = true\n").unwrap();
+    fixture.cmd().args(["tune", "--device", MX3S, "--dpi", "1200"]).assert().success();
+
+    let json = fixture.backups().into_iter().find(|backup| backup.extension().is_some_and(|e| e == "json")).unwrap();
+    let json = std::fs::read_to_string(json).unwrap();
+    assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), original());
+    assert!(json.starts_with(r#"{"ever_connected_devices":{"devices":[{"connectionType":"BOLT""#), "{json}");
+}
+
+#[test]
+fn import_prune_removes_assignments_missing_in_file() {
+    let fixture = Fixture::new();
+    let exported = fixture.path("exported.json");
+    fixture.cmd().arg("export").arg(&exported).assert().success();
+    let mut file: Value = serde_json::from_slice(&std::fs::read(&exported).unwrap()).unwrap();
+    file["profile-default"]["assignments"].as_array_mut().unwrap().retain(|a| a["slotId"] != "mx-master-3s-2b034_c83");
+    std::fs::write(&exported, serde_json::to_vec(&file).unwrap()).unwrap();
+
+    fixture.cmd().arg("import").arg(&exported).assert().success();
+    assert_eq!(fixture.settings(), SETTINGS.as_bytes());
+    fixture.cmd().arg("import").arg(&exported).arg("--prune").assert().success();
+    let settings = fixture.settings_json();
+    assert_eq!(slot_ids(&settings, "profile-default", MX3S), [format!("{MX3S}_c195"), format!("{MX3S}_c82")]);
+    assert_eq!(slot_ids(&settings, "profile-default", MX3), [format!("{MX3}_c82")]);
+    assert_eq!(settings["profile-safari"], original()["profile-safari"]);
+}
+
+#[test]
+fn preset_saved_from_one_device_is_applied_to_another() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["preset", "save", "work", "--device", MX3S, "--with-settings"]).assert().success();
+    fixture.cmd().args(["preset", "save", "work", "--device", MX3S]).assert().failure();
+    let output = fixture.cmd().args(["preset", "list"]).assert().success().get_output().stdout.clone();
+    assert!(String::from_utf8(output).unwrap().contains("work"));
+
+    fixture.cmd().args(["preset", "apply", "work", "--device", MX3]).assert().success();
+    let settings = fixture.settings_json();
+    let expected: Vec<String> = slot_ids(&settings, "profile-default", MX3S).iter().map(|s| s.replace(MX3S, MX3)).collect();
+    assert_eq!(slot_ids(&settings, "profile-default", MX3), expected);
+    assert_eq!(settings["profile-default"]["mx-master-3-6b023_dpi"], 1600);
+}
+
+#[test]
+fn buttons_are_named_with_configured_names() {
+    let fixture = Fixture::new();
+    let config = fixture.path("home/.config/logi-man/config.toml");
+    std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+    std::fs::write(&config, "[buttons.2b034]\nc86 = \"forward (thumb)\"\n").unwrap();
+
+    let output = fixture.cmd().args(["buttons", "--device", MX3S]).assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.lines().any(|line| line.starts_with("c83") && line.contains("back") && line.contains("mission-control")), "{output}");
+    assert!(output.lines().any(|line| line.starts_with("c86") && line.contains("forward (thumb)")), "{output}");
+}
+
+#[test]
+fn watch_records_external_changes() {
+    let fixture = Fixture::new();
+    let db = fixture.db.clone();
+    let change = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        let edited = SETTINGS.replace(r#""mx-master-3s-2b034_dpi": 1600,"#, r#""mx-master-3s-2b034_dpi": 1000,"#);
+        let conn = rusqlite::Connection::open(db).unwrap();
+        conn.execute("UPDATE data SET file = ?1 WHERE _id = 1", [edited.as_bytes()]).unwrap();
+    });
+    // Watch runs until killed.
+    let _ = fixture.cmd().args(["watch", "--interval", "0.1"]).timeout(std::time::Duration::from_millis(1500)).output();
+    change.join().unwrap();
+
+    let output = fixture.cmd().args(["history", "log", "--source", "watch"]).assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(output.lines().count(), 1, "{output}");
+    assert!(output.contains("GUI edit, 1 value(s) changed"), "{output}");
+    let output = fixture.cmd().args(["history", "show", "1"]).assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("/profile-default/mx-master-3s-2b034_dpi"), "{output}");
+}
+
+#[test]
+fn backup_list_shows_backups_oldest_first() {
+    let fixture = Fixture::new();
+    fixture.cmd().args(["backup", "list"]).assert().success().stdout("");
+    for dpi in ["1000", "1200"] {
+        fixture.cmd().args(["tune", "--device", MX3S, "--dpi", dpi]).assert().success();
+    }
+    let output = fixture.cmd().args(["backup", "list"]).assert().success().get_output().stdout.clone();
+    let listed: Vec<String> = String::from_utf8(output).unwrap().lines().map(str::to_owned).collect();
+    let backups: Vec<String> = fixture.backups().iter().map(|backup| backup.display().to_string()).collect();
+    assert_eq!(listed, backups);
+}
+
+#[test]
+fn compare_devices_shows_what_transfer_would_lose() {
+    let fixture = Fixture::new();
+    let output = fixture.cmd().args(["compare-devices", MX3S, MX3]).assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    // Columns are aligned with spaces, they are squeezed for comparison.
+    let row = |button: &str| {
+        let line = output.lines().find(|line| line.starts_with(button)).unwrap_or_default();
+        line.split_whitespace().collect::<Vec<_>>().join(" ")
+    };
+    assert_eq!(row("c83"), "c83 (back) mission-control default");
+    assert_eq!(row("c86"), "c86 (forward) default (+1 in app profiles) default");
+    assert!(output.contains("only on mx-master-3s-2b034: dpi"), "{output}");
+    assert!(output.contains("left default: c196 (mode shift button)"), "{output}");
+    assert_eq!(fixture.settings(), SETTINGS.as_bytes());
+}
+
+#[test]
+fn database_is_taken_from_option_environment_or_configuration() {
+    let (configured, from_env, given) = (Fixture::new(), Fixture::new(), Fixture::new());
+    let config = given.path("home/.config/logi-man/config.toml");
+    std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+    std::fs::write(&config, format!("db = {:?}\n", configured.db.to_str().unwrap())).unwrap();
+    let status = |env: Option<&Fixture>, option: Option<&Fixture>| {
+        let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("logi-man");
+        cmd.env("HOME", given.path("home")).env("XDG_CONFIG_HOME", given.path("home/.config")).env_remove("LOGI_MAN_DB");
+        if let Some(fixture) = env {
+            cmd.env("LOGI_MAN_DB", &fixture.db);
+        }
+        if let Some(fixture) = option {
+            cmd.arg("--db").arg(&fixture.db);
+        }
+        let output = cmd.arg("status").assert().success().get_output().stdout.clone();
+        String::from_utf8(output).unwrap()
+    };
+
+    let database = |fixture: &Fixture| format!("Database:     {}\n", fixture.db.display());
+    assert!(status(None, None).starts_with(&database(&configured)));
+    assert!(status(Some(&from_env), None).starts_with(&database(&from_env)));
+    assert!(status(Some(&from_env), Some(&given)).starts_with(&database(&given)));
+}
+
+/// Agent is only controlled on macOS and Windows, it is asked to be
+/// restarted manually elsewhere.
+#[cfg(not(any(target_os = "macos", windows)))]
+#[test]
+fn agent_control_is_reported_unsupported() {
+    let fixture = Fixture::new();
+    let output = fixture.cmd().args(["agent", "status"]).assert().failure().get_output().stderr.clone();
+    assert!(String::from_utf8(output).unwrap().contains("isn't supported on this platform"));
+    fixture.cmd().arg("restart-agent").assert().success().stdout("Now you should kill Logi agent\n");
+}
+
+#[test]
+fn agent_is_restarted_after_change_unless_told_not_to() {
+    let fixture = Fixture::new();
+    let output = fixture.cmd().args(["tune", "--device", MX3S, "--dpi", "1000"]).assert().success().get_output().stdout.clone();
+    assert!(String::from_utf8(output).unwrap().contains("run `logi-man restart-agent` to apply them"));
+    let output = fixture.cmd().args(["tune", "--device", MX3S, "--dpi", "1200", "--dry-run"]).assert().success().get_output().stdout.clone();
+    assert!(!String::from_utf8(output).unwrap().contains("restart-agent"));
+}
@@ -0,0 +1,88 @@
+//! Fixture settings databases for integration tests.
+
+use std::path::{Path, PathBuf};
+
+use assert_cmd::Command;
+use serde_json::Value;
+use tempfile::TempDir;
+
+/// Settings used by default, with unknown fields on every level.
+pub const SETTINGS: &str = include_str!("../fixtures/settings.json");
+
+/// Settings database in temporary directory, together with home directory
+/// isolating logi-man configuration and history from user ones.
+pub struct Fixture {
+    dir: TempDir,
+    pub db: PathBuf,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        Self::with_settings(SETTINGS.as_bytes())
+    }
+
+    /// Creates database laid out like Options+ one, holding given settings.
+    pub fn with_settings(settings: &[u8]) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("settings.db");
+        let conn = rusqlite::Connection::open(&db).unwrap();
+        conn.execute_batch("CREATE TABLE data (_id INTEGER PRIMARY KEY, _date_created DATETIME, file BLOB)").unwrap();
+        conn.execute("INSERT INTO data VALUES (1, datetime('now'), ?1)", [settings]).unwrap();
+        std::fs::create_dir(dir.path().join("home")).unwrap();
+        Fixture { dir, db }
+    }
+
+    /// Returns logi-man command working with fixture database. Agent isn't
+    /// restarted, there is none in tests.
+    pub fn cmd(&self) -> Command {
+        let home = self.dir.path().join("home");
+        let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("logi-man");
+        cmd.env("HOME", &home)
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .env("XDG_DATA_HOME", home.join(".local/share"))
+            .env_remove("LOGI_MAN_DB")
+            .arg("--db").arg(&self.db)
+            .arg("--no-restart");
+        cmd
+    }
+
+    /// Returns settings blob as it is stored in database.
+    pub fn settings(&self) -> Vec<u8> {
+        let conn = rusqlite::Connection::open(&self.db).unwrap();
+        conn.query_row("SELECT file FROM data WHERE _id = 1", [], |row| row.get(0)).unwrap()
+    }
+
+    pub fn settings_json(&self) -> Value {
+        serde_json::from_slice(&self.settings()).unwrap()
+    }
+
+    /// Returns file in fixture directory.
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    /// Returns backups made next to database.
+    pub fn backups(&self) -> Vec<PathBuf> {
+        let mut backups: Vec<PathBuf> = std::fs::read_dir(self.dir.path()).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| is_backup(path))
+            .collect();
+        backups.sort();
+        backups
+    }
+}
+
+fn is_backup(path: &Path) -> bool {
+    path.file_name().unwrap().to_str().unwrap().starts_with("settings.db.")
+}
+
+/// Returns slot ids of device assignments in profile.
+pub fn slot_ids(settings: &Value, profile: &str, device: &str) -> Vec<String> {
+    let prefix = format!("{device}_");
+    let mut slot_ids: Vec<String> = settings[profile]["assignments"].as_array().unwrap().iter()
+        .map(|a| a["slotId"].as_str().unwrap().to_owned())
+        .filter(|slot_id| slot_id.starts_with(&prefix))
+        .collect();
+    slot_ids.sort();
+    slot_ids
+}